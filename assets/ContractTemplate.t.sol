@@ -2,11 +2,13 @@
 pragma solidity 0.8.10;
 
 import "ds-test/test.sol";
+import "./utils/console.sol";
 
 contract ContractTest is DSTest {
     function setUp() public {}
 
     function testExample() public {
+        console.log("Hello world");
         assertTrue(true);
     }
 }
@@ -0,0 +1,172 @@
+//! L2 specific transaction handling
+use ethers_core::types::{Address, Eip1559TransactionRequest, TransactionRequest, U256};
+use std::fmt;
+
+/// Optimism's `GasPriceOracle` predeploy, used to compute the L1 data fee of a transaction
+pub const OPTIMISM_GAS_PRICE_ORACLE: &str = "0x420000000000000000000000000000000000000F";
+
+/// Arbitrum's `NodeInterface` precompile, used to split gas estimates into their L1 and L2 part
+pub const ARBITRUM_NODE_INTERFACE: &str = "0x00000000000000000000000000000000000000C8";
+
+/// Receipt fields Optimism adds to report the L1 data fee
+const OPTIMISM_RECEIPT_FIELDS: &[&str] = &["l1Fee", "l1GasUsed", "l1GasPrice", "l1FeeScalar"];
+
+/// Receipt fields Arbitrum adds to report the L1 part of the gas used
+const ARBITRUM_RECEIPT_FIELDS: &[&str] = &["gasUsedForL1", "l1BlockNumber"];
+
+/// Rollups whose transactions need special handling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum L2Chain {
+    Optimism,
+    Arbitrum,
+}
+
+impl L2Chain {
+    /// Returns the rollup the chain id belongs to, if any
+    pub fn from_chain_id(chain_id: u64) -> Option<Self> {
+        match chain_id {
+            // mainnet, kovan, goerli
+            10 | 69 | 420 => Some(L2Chain::Optimism),
+            // one, nova, rinkeby, goerli
+            42161 | 42170 | 421611 | 421613 => Some(L2Chain::Arbitrum),
+            _ => None,
+        }
+    }
+
+    /// Whether transactions on the chain should be sent as legacy transactions by default because
+    /// it does not accept EIP-1559 transactions. That is the case for the rollups which were not
+    /// upgraded to Bedrock or Nitro, i.e. Optimism Kovan and Arbitrum Rinkeby.
+    pub fn legacy(chain_id: u64) -> bool {
+        matches!(chain_id, 69 | 421611)
+    }
+
+    /// The receipt fields which report the L1 part of the transaction's cost
+    pub fn receipt_fields(&self) -> &'static [&'static str] {
+        match self {
+            L2Chain::Optimism => OPTIMISM_RECEIPT_FIELDS,
+            L2Chain::Arbitrum => ARBITRUM_RECEIPT_FIELDS,
+        }
+    }
+
+    /// Extracts the L2 specific fields of a json receipt, in the order of
+    /// [`L2Chain::receipt_fields`]
+    pub fn l1_receipt_fields(&self, receipt: &serde_json::Value) -> Vec<(&'static str, String)> {
+        self.receipt_fields()
+            .iter()
+            .filter_map(|field| {
+                let value = receipt.get(field)?;
+                let value = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+                Some((*field, value))
+            })
+            .collect()
+    }
+}
+
+/// Converts the request into a legacy transaction, using the max fee as gas price. The chain id
+/// is kept so that the transaction stays replay protected as per EIP-155
+pub fn to_legacy(tx: Eip1559TransactionRequest) -> TransactionRequest {
+    TransactionRequest {
+        from: tx.from,
+        to: tx.to,
+        gas: tx.gas,
+        gas_price: tx.max_fee_per_gas,
+        value: tx.value,
+        data: tx.data,
+        nonce: tx.nonce,
+        chain_id: tx.chain_id,
+        ..Default::default()
+    }
+}
+
+pub(crate) fn address(s: &str) -> Address {
+    s.parse().expect("valid address")
+}
+
+/// The estimated cost of a transaction, split into its execution and L1 data part
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TxCost {
+    /// The gas used for execution
+    pub gas: U256,
+    /// The price paid per unit of execution gas
+    pub gas_price: U256,
+    /// The gas used to post the transaction's data to L1, zero on L1s
+    pub l1_gas: U256,
+    /// The fee charged for posting the transaction's data to L1, in wei
+    pub l1_fee: U256,
+}
+
+impl TxCost {
+    /// The total fee of the transaction, in wei
+    pub fn total_fee(&self) -> U256 {
+        self.gas * self.gas_price + self.l1_fee
+    }
+}
+
+impl fmt::Display for TxCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "gas          {}", self.gas)?;
+        writeln!(f, "gasPrice     {}", self.gas_price)?;
+        writeln!(f, "l1Gas        {}", self.l1_gas)?;
+        writeln!(f, "l1Fee        {}", self.l1_fee)?;
+        write!(f, "totalFee     {}", self.total_fee())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_detect_l2_chains() {
+        assert_eq!(L2Chain::from_chain_id(10), Some(L2Chain::Optimism));
+        assert_eq!(L2Chain::from_chain_id(42161), Some(L2Chain::Arbitrum));
+        assert_eq!(L2Chain::from_chain_id(1), None);
+
+        assert!(L2Chain::legacy(69));
+        assert!(L2Chain::legacy(421611));
+        // Bedrock and Nitro chains accept EIP-1559 transactions
+        assert!(!L2Chain::legacy(10));
+        assert!(!L2Chain::legacy(420));
+        assert!(!L2Chain::legacy(42161));
+        assert!(!L2Chain::legacy(42170));
+        assert!(!L2Chain::legacy(421613));
+    }
+
+    #[test]
+    fn keeps_chain_id_of_legacy_transactions() {
+        let tx = Eip1559TransactionRequest::new().chain_id(10u64).max_fee_per_gas(7u64);
+        let legacy = to_legacy(tx);
+        assert_eq!(legacy.chain_id, Some(10u64.into()));
+        assert_eq!(legacy.gas_price, Some(7u64.into()));
+    }
+
+    #[test]
+    fn can_extract_receipt_fields() {
+        let receipt = serde_json::json!({
+            "status": "0x1",
+            "l1Fee": "0x2a",
+            "l1GasUsed": "0x640",
+            "l1FeeScalar": "1.5",
+        });
+        assert_eq!(
+            L2Chain::Optimism.l1_receipt_fields(&receipt),
+            vec![
+                ("l1Fee", "0x2a".to_string()),
+                ("l1GasUsed", "0x640".to_string()),
+                ("l1FeeScalar", "1.5".to_string())
+            ]
+        );
+        assert!(L2Chain::Arbitrum.l1_receipt_fields(&receipt).is_empty());
+    }
+
+    #[test]
+    fn adds_l1_fee_to_total() {
+        let cost = TxCost {
+            gas: 21_000u64.into(),
+            gas_price: 2u64.into(),
+            l1_gas: 1_000u64.into(),
+            l1_fee: 500u64.into(),
+        };
+        assert_eq!(cost.total_fee(), 42_500u64.into());
+    }
+}
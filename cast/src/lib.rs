@@ -4,7 +4,13 @@
 use chrono::NaiveDateTime;
 use ethers_core::{
     abi::{Abi, AbiParser, Token},
-    types::{Chain, *},
+    types::{
+        transaction::{
+            eip2718::TypedTransaction,
+            eip2930::{AccessList, Eip2930TransactionRequest},
+        },
+        Chain, *,
+    },
     utils::{self, keccak256},
 };
 
@@ -16,6 +22,12 @@ use std::str::FromStr;
 
 use foundry_utils::{encode_args, get_func, get_func_etherscan, to_table};
 
+pub mod l2;
+use l2::{L2Chain, TxCost};
+
+pub mod tx;
+use tx::{TxOptions, TxType};
+
 // TODO: CastContract with common contract initializers? Same for CastProviders?
 
 pub struct Cast<M> {
@@ -139,13 +151,105 @@ where
         args: Option<(&str, Vec<String>)>,
         chain: Chain,
         etherscan_api_key: Option<String>,
+    ) -> Result<PendingTransaction<'_, M::Provider>> {
+        self.send_with(from, to, args, chain, etherscan_api_key, TxOptions::default()).await
+    }
+
+    /// Sends a transaction of the type configured in the [`TxOptions`], see [`Cast::send`].
+    ///
+    /// Blob transactions are sent to the node with `eth_sendTransaction`, so `from` must be
+    /// an account the node can sign for.
+    pub async fn send_with<F: Into<NameOrAddress>, T: Into<NameOrAddress>>(
+        &self,
+        from: F,
+        to: T,
+        args: Option<(&str, Vec<String>)>,
+        chain: Chain,
+        etherscan_api_key: Option<String>,
+        opts: TxOptions,
     ) -> Result<PendingTransaction<'_, M::Provider>> {
         let (tx, _) = self.build_tx(from, to, args, chain, etherscan_api_key).await?;
+        let chain_id = self.chain_id().await?.as_u64();
+        let mut tx = tx.chain_id(chain_id);
+        let tx_type = opts.resolve_tx_type(L2Chain::legacy(chain_id))?;
+
+        let tx: TypedTransaction = match tx_type {
+            TxType::Legacy => l2::to_legacy(tx).into(),
+            TxType::AccessList => {
+                let tx = l2::to_legacy(tx);
+                let access_list = match opts.access_list {
+                    Some(access_list) => access_list,
+                    None => self.create_access_list(&tx.clone().into()).await?,
+                };
+                Eip2930TransactionRequest::new(tx, access_list).into()
+            }
+            TxType::Eip1559 => {
+                if let Some(access_list) = opts.access_list {
+                    tx = tx.access_list(access_list);
+                }
+                tx.into()
+            }
+            TxType::Blob => return self.send_blob(tx, opts).await,
+        };
         let res = self.provider.send_transaction(tx, None).await?;
 
         Ok::<_, eyre::Error>(res)
     }
 
+    /// Creates the access list of the transaction with `eth_createAccessList`
+    pub async fn create_access_list(&self, tx: &TypedTransaction) -> Result<AccessList> {
+        let res: serde_json::Value =
+            self.provider.provider().request("eth_createAccessList", (tx, "latest")).await?;
+        if let Some(err) = res.get("error").and_then(|err| err.as_str()) {
+            eyre::bail!("failed to create access list: {}", err)
+        }
+        let access_list =
+            res.get("accessList").cloned().ok_or_else(|| eyre::eyre!("no access list returned"))?;
+        Ok(serde_json::from_value(access_list)?)
+    }
+
+    /// Sends a blob transaction, which is not supported by the transaction types of ethers, to
+    /// the node for signing
+    async fn send_blob(
+        &self,
+        tx: Eip1559TransactionRequest,
+        opts: TxOptions,
+    ) -> Result<PendingTransaction<'_, M::Provider>> {
+        let sidecar = opts.blobs.ok_or_else(|| eyre::eyre!("blob transactions need blobs"))?;
+        sidecar.validate()?;
+        if tx.to.is_none() {
+            eyre::bail!("blob transactions cannot create contracts")
+        }
+
+        let mut request = serde_json::to_value(&tx)?;
+        let fields = request.as_object_mut().expect("transaction is serialized as an object");
+        fields.insert("type".to_string(), format!("{:#x}", TxType::Blob.id()).into());
+        if let Some(access_list) = opts.access_list {
+            fields.insert("accessList".to_string(), serde_json::to_value(access_list)?);
+        }
+        let max_fee_per_blob_gas = match opts.max_fee_per_blob_gas {
+            Some(fee) => fee,
+            None => {
+                self.provider
+                    .provider()
+                    .request("eth_blobBaseFee", Vec::<serde_json::Value>::new())
+                    .await?
+            }
+        };
+        fields.insert("maxFeePerBlobGas".to_string(), serde_json::to_value(max_fee_per_blob_gas)?);
+        fields.insert(
+            "blobVersionedHashes".to_string(),
+            serde_json::to_value(sidecar.versioned_hashes())?,
+        );
+        fields.insert("blobs".to_string(), serde_json::to_value(&sidecar.blobs)?);
+        fields.insert("commitments".to_string(), serde_json::to_value(&sidecar.commitments)?);
+        fields.insert("proofs".to_string(), serde_json::to_value(&sidecar.proofs)?);
+
+        let tx_hash: TxHash =
+            self.provider.provider().request("eth_sendTransaction", [request]).await?;
+        Ok(PendingTransaction::new(tx_hash, self.provider.provider()))
+    }
+
     /// Estimates the gas cost of a transaction
     ///
     /// ```no_run
@@ -181,6 +285,110 @@ where
         Ok::<_, eyre::Error>(res)
     }
 
+    /// Estimates the total cost of a transaction, including the fee charged by rollups for
+    /// posting the transaction's data to L1
+    ///
+    /// ```no_run
+    /// use cast::Cast;
+    /// use ethers_core::types::{Address, Chain};
+    /// use ethers_providers::{Provider, Http};
+    /// use std::{str::FromStr, convert::TryFrom};
+    ///
+    /// # async fn foo() -> eyre::Result<()> {
+    /// let provider = Provider::<Http>::try_from("http://localhost:8545")?;
+    /// let cast = Cast::new(provider);
+    /// let from = Address::from_str("0x7eD52863829AB99354F3a0503A622e82AcD5F7d3")?;
+    /// let to = Address::from_str("0xB3C95ff08316fb2F2e3E52Ee82F8e7b605Aa1304")?;
+    /// let sig = "greet(string)()";
+    /// let args = vec!["5".to_owned()];
+    /// let cost = cast.estimate_cost(from, to, Some((sig, args)), Chain::Mainnet, None).await?;
+    /// println!("{}", cost);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn estimate_cost<F: Into<NameOrAddress>, T: Into<NameOrAddress>>(
+        &self,
+        from: F,
+        to: T,
+        args: Option<(&str, Vec<String>)>,
+        chain: Chain,
+        etherscan_api_key: Option<String>,
+    ) -> Result<TxCost> {
+        let (tx, _) = self.build_tx(from, to, args, chain, etherscan_api_key).await?;
+        let gas = self.provider.estimate_gas(&tx.clone().into()).await?;
+        let gas_price = self.provider.get_gas_price().await?;
+        let mut cost = TxCost { gas, gas_price, ..Default::default() };
+
+        match self.l2_chain().await? {
+            Some(L2Chain::Optimism) => {
+                // the L1 fee is charged for the signed transaction, as posted to L1
+                let mut legacy = l2::to_legacy(tx).gas(gas).gas_price(gas_price);
+                if let Some(from) = legacy.from {
+                    legacy = legacy.nonce(self.provider.get_transaction_count(from, None).await?);
+                }
+                let rlp = legacy.rlp(self.chain_id().await?.as_u64());
+                let oracle = l2::address(l2::OPTIMISM_GAS_PRICE_ORACLE);
+                cost.l1_fee = self
+                    .call_uint(
+                        oracle,
+                        "function getL1Fee(bytes) view returns (uint256)",
+                        &[Token::Bytes(rlp.to_vec())],
+                    )
+                    .await?;
+                cost.l1_gas = self
+                    .call_uint(
+                        oracle,
+                        "function getL1GasUsed(bytes) view returns (uint256)",
+                        &[Token::Bytes(rlp.to_vec())],
+                    )
+                    .await?;
+            }
+            Some(L2Chain::Arbitrum) => {
+                // Arbitrum's estimate includes the L1 part, the node interface splits it up
+                let func = AbiParser::default().parse_function(
+                    "function gasEstimateComponents(address,bool,bytes) returns (uint64,uint64,uint256,uint256)",
+                )?;
+                let to = match tx.to {
+                    Some(NameOrAddress::Address(to)) => to,
+                    _ => Address::zero(),
+                };
+                let data = func.encode_input(&[
+                    Token::Address(to),
+                    Token::Bool(tx.to.is_none()),
+                    Token::Bytes(tx.data.clone().unwrap_or_default().to_vec()),
+                ])?;
+                let call = Eip1559TransactionRequest::new()
+                    .to(l2::address(l2::ARBITRUM_NODE_INTERFACE))
+                    .data(data)
+                    .into();
+                let res = self.provider.call(&call, None).await?;
+                let tokens = func.decode_output(res.as_ref())?;
+                let uint = |idx: usize| tokens.get(idx).cloned().and_then(Token::into_uint);
+                if let (Some(total), Some(l1_gas), Some(base_fee)) = (uint(0), uint(1), uint(2)) {
+                    cost.gas = total.saturating_sub(l1_gas);
+                    cost.gas_price = base_fee;
+                    cost.l1_gas = l1_gas;
+                    cost.l1_fee = l1_gas * base_fee;
+                }
+            }
+            None => {}
+        }
+
+        Ok(cost)
+    }
+
+    /// Calls a view function returning a single uint
+    async fn call_uint(&self, to: Address, sig: &str, args: &[Token]) -> Result<U256> {
+        let func = AbiParser::default().parse_function(sig)?;
+        let tx = Eip1559TransactionRequest::new().to(to).data(func.encode_input(args)?).into();
+        let res = self.provider.call(&tx, None).await?;
+        func.decode_output(res.as_ref())?
+            .into_iter()
+            .next()
+            .and_then(Token::into_uint)
+            .ok_or_else(|| eyre::eyre!("{} did not return a uint", sig))
+    }
+
     async fn build_tx<F: Into<NameOrAddress>, T: Into<NameOrAddress>>(
         &self,
         from: F,
@@ -371,6 +579,23 @@ where
         Ok(self.provider.get_chainid().await?)
     }
 
+    /// Returns the rollup the provider is connected to, if any
+    pub async fn l2_chain(&self) -> Result<Option<L2Chain>> {
+        Ok(L2Chain::from_chain_id(self.chain_id().await?.as_u64()))
+    }
+
+    /// Returns the rollup specific fields of the receipt reporting the L1 part of the
+    /// transaction's cost, empty if the provider is not connected to a rollup
+    pub async fn l1_receipt_fields(&self, tx_hash: H256) -> Result<Vec<(&'static str, String)>> {
+        let l2 = match self.l2_chain().await? {
+            Some(l2) => l2,
+            None => return Ok(Vec::new()),
+        };
+        let receipt: serde_json::Value =
+            self.provider.provider().request("eth_getTransactionReceipt", [tx_hash]).await?;
+        Ok(l2.l1_receipt_fields(&receipt))
+    }
+
     pub async fn block_number(&self) -> Result<U64> {
         Ok(self.provider.get_block_number().await?)
     }
@@ -445,19 +670,24 @@ where
         field: Option<String>,
         to_json: bool,
     ) -> Result<String> {
-        let transaction_result = self
+        // requested as raw json so that transaction types unknown to ethers, e.g. Optimism's
+        // deposit transactions, and chain specific fields are preserved
+        let transaction_result: serde_json::Value = self
             .provider
-            .get_transaction(H256::from_str(&tx_hash)?)
-            .await?
-            .ok_or_else(|| eyre::eyre!("transaction {:?} not found", tx_hash))?;
+            .provider()
+            .request("eth_getTransactionByHash", [H256::from_str(&tx_hash)?])
+            .await?;
+        if transaction_result.is_null() {
+            eyre::bail!("transaction {:?} not found", tx_hash)
+        }
 
         let transaction = if let Some(ref field) = field {
-            serde_json::to_value(&transaction_result)?
+            transaction_result
                 .get(field)
                 .cloned()
                 .ok_or_else(|| eyre::eyre!("field {} not found", field))?
         } else {
-            serde_json::to_value(&transaction_result)?
+            transaction_result
         };
 
         let transaction =
@@ -0,0 +1,223 @@
+//! Transaction types and the options to construct them
+use ethers_core::types::{transaction::eip2930::AccessList, Bytes, H256, U256};
+use eyre::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::{path::Path, str::FromStr};
+
+/// Size of a single blob, as per EIP-4844
+pub const BLOB_SIZE: usize = 4096 * 32;
+
+/// Size of a KZG commitment or proof
+const KZG_SIZE: usize = 48;
+
+/// Version byte of the blob versioned hashes
+const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+/// The EIP-2718 type of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxType {
+    /// Pre EIP-2718 transactions
+    Legacy,
+    /// EIP-2930 transactions, carrying an access list
+    AccessList,
+    /// EIP-1559 transactions, with a priority fee
+    Eip1559,
+    /// EIP-4844 transactions, carrying blobs
+    Blob,
+}
+
+impl TxType {
+    /// The EIP-2718 transaction type id
+    pub fn id(&self) -> u8 {
+        match self {
+            TxType::Legacy => 0,
+            TxType::AccessList => 1,
+            TxType::Eip1559 => 2,
+            TxType::Blob => 3,
+        }
+    }
+}
+
+impl FromStr for TxType {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "0" | "legacy" => TxType::Legacy,
+            "1" | "2930" | "access-list" => TxType::AccessList,
+            "2" | "1559" | "eip1559" => TxType::Eip1559,
+            "3" | "4844" | "blob" => TxType::Blob,
+            other => eyre::bail!("unknown transaction type {}", other),
+        })
+    }
+}
+
+/// The blobs of a blob transaction, along with their KZG commitments and proofs.
+///
+/// These are not part of the signed transaction, which only commits to the blobs through their
+/// versioned hashes, but must be sent along with it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlobSidecar {
+    pub blobs: Vec<Bytes>,
+    pub commitments: Vec<Bytes>,
+    pub proofs: Vec<Bytes>,
+}
+
+impl BlobSidecar {
+    /// Reads a sidecar from a json file of the form
+    /// `{"blobs": ["0x.."], "commitments": ["0x.."], "proofs": ["0x.."]}`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read blob sidecar {}", path.display()))?;
+        Self::from_json(&json)
+    }
+
+    /// Parses the json representation of a sidecar, see [`BlobSidecar::load`]
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let field = |name: &str| -> Result<Vec<Bytes>> {
+            value
+                .get(name)
+                .and_then(|v| v.as_array())
+                .ok_or_else(|| eyre::eyre!("blob sidecar is missing `{}`", name))?
+                .iter()
+                .map(|item| {
+                    let item = item.as_str().ok_or_else(|| eyre::eyre!("expected hex string"))?;
+                    Ok(hex::decode(item.trim_start_matches("0x"))?.into())
+                })
+                .collect()
+        };
+        let sidecar = Self {
+            blobs: field("blobs")?,
+            commitments: field("commitments")?,
+            proofs: field("proofs")?,
+        };
+        sidecar.validate()?;
+        Ok(sidecar)
+    }
+
+    /// Checks that every blob has a commitment and a proof of the right size
+    pub fn validate(&self) -> Result<()> {
+        if self.blobs.is_empty() {
+            eyre::bail!("blob transactions must carry at least one blob")
+        }
+        if self.blobs.len() != self.commitments.len() || self.blobs.len() != self.proofs.len() {
+            eyre::bail!(
+                "got {} blobs, {} commitments and {} proofs",
+                self.blobs.len(),
+                self.commitments.len(),
+                self.proofs.len()
+            )
+        }
+        if let Some(blob) = self.blobs.iter().find(|blob| blob.len() != BLOB_SIZE) {
+            eyre::bail!("blobs must be {} bytes, got {}", BLOB_SIZE, blob.len())
+        }
+        if let Some(kzg) =
+            self.commitments.iter().chain(&self.proofs).find(|kzg| kzg.len() != KZG_SIZE)
+        {
+            eyre::bail!("commitments and proofs must be {} bytes, got {}", KZG_SIZE, kzg.len())
+        }
+        Ok(())
+    }
+
+    /// The versioned hashes of the blobs, which the transaction commits to
+    pub fn versioned_hashes(&self) -> Vec<H256> {
+        self.commitments
+            .iter()
+            .map(|commitment| {
+                let mut hash: [u8; 32] = Sha256::digest(commitment.as_ref()).into();
+                hash[0] = VERSIONED_HASH_VERSION_KZG;
+                H256(hash)
+            })
+            .collect()
+    }
+}
+
+/// Options controlling the type and type specific fields of a transaction
+#[derive(Debug, Clone, Default)]
+pub struct TxOptions {
+    /// The transaction type, defaults to EIP-1559, or legacy on rollups which do not support it
+    pub tx_type: Option<TxType>,
+    /// The access list to include. If not set for an access list transaction, it is created
+    /// by the node with `eth_createAccessList`
+    pub access_list: Option<AccessList>,
+    /// The maximum fee per blob gas of a blob transaction
+    pub max_fee_per_blob_gas: Option<U256>,
+    /// The blobs of a blob transaction
+    pub blobs: Option<BlobSidecar>,
+}
+
+impl TxOptions {
+    /// The type of the transaction, inferred from the provided fields if not set explicitly
+    pub fn tx_type(&self) -> Option<TxType> {
+        self.tx_type.or_else(|| if self.blobs.is_some() { Some(TxType::Blob) } else { None })
+    }
+
+    /// The type of the transaction to send on a chain, which defaults to legacy transactions if
+    /// `legacy_chain` is set, or to EIP-2930 transactions there if an access list is provided
+    pub fn resolve_tx_type(&self, legacy_chain: bool) -> Result<TxType> {
+        Ok(match self.tx_type() {
+            Some(TxType::Legacy) if self.access_list.is_some() => {
+                eyre::bail!(
+                    "legacy transactions can not carry an access list, use `--type access-list`"
+                )
+            }
+            Some(tx_type) => tx_type,
+            None if legacy_chain && self.access_list.is_some() => TxType::AccessList,
+            None if legacy_chain => TxType::Legacy,
+            None => TxType::Eip1559,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_tx_types() {
+        assert_eq!("access-list".parse::<TxType>().unwrap(), TxType::AccessList);
+        assert_eq!("1".parse::<TxType>().unwrap(), TxType::AccessList);
+        assert_eq!("4844".parse::<TxType>().unwrap(), TxType::Blob);
+        assert_eq!("Legacy".parse::<TxType>().unwrap().id(), 0);
+        assert!("5".parse::<TxType>().is_err());
+    }
+
+    #[test]
+    fn keeps_access_lists_on_legacy_chains() {
+        let opts = TxOptions::default();
+        assert_eq!(opts.resolve_tx_type(false).unwrap(), TxType::Eip1559);
+        assert_eq!(opts.resolve_tx_type(true).unwrap(), TxType::Legacy);
+
+        let opts = TxOptions { access_list: Some(AccessList::default()), ..Default::default() };
+        assert_eq!(opts.resolve_tx_type(false).unwrap(), TxType::Eip1559);
+        assert_eq!(opts.resolve_tx_type(true).unwrap(), TxType::AccessList);
+
+        let opts = TxOptions { tx_type: Some(TxType::Legacy), ..opts };
+        assert!(opts.resolve_tx_type(false).is_err());
+        assert!(opts.resolve_tx_type(true).is_err());
+    }
+
+    #[test]
+    fn can_compute_versioned_hashes() {
+        // commitment to the zero blob
+        let mut commitment = vec![0u8; KZG_SIZE];
+        commitment[0] = 0xc0;
+        let sidecar = BlobSidecar {
+            blobs: vec![vec![0u8; BLOB_SIZE].into()],
+            commitments: vec![commitment.clone().into()],
+            proofs: vec![commitment.into()],
+        };
+        sidecar.validate().unwrap();
+        assert_eq!(
+            sidecar.versioned_hashes(),
+            vec!["010657f37554c781402a22917dee2f75def7ab966d7b770905398eba3c444014"
+                .parse::<H256>()
+                .unwrap()]
+        );
+
+        let invalid = BlobSidecar { proofs: vec![], ..sidecar };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -2,7 +2,10 @@ pub mod cmd;
 
 mod utils;
 
-use cast::{Cast, SimpleCast};
+use cast::{
+    tx::{TxOptions, TxType},
+    Cast, SimpleCast,
+};
 
 mod opts;
 use cast::InterfacePath;
@@ -172,11 +175,15 @@ async fn main() -> eyre::Result<()> {
             let provider = Provider::try_from(rpc_url)?;
             println!("{}", Cast::new(&provider).transaction(hash, field, to_json).await?)
         }
-        Subcommands::SendTx { eth, to, sig, cast_async, args } => {
+        Subcommands::SendTx { eth, to, sig, cast_async, args, tx } => {
             let provider = Provider::try_from(eth.rpc_url()?)?;
             let chain_id = Cast::new(&provider).chain_id().await?;
+            let tx = tx.options()?;
 
             if let Some(signer) = eth.signer_with(chain_id, provider.clone()).await? {
+                if tx.tx_type() == Some(TxType::Blob) {
+                    eyre::bail!("blob transactions are signed by the node, use --from with an account unlocked on the node")
+                }
                 match signer {
                     WalletType::Ledger(signer) => {
                         cast_send(
@@ -186,6 +193,7 @@ async fn main() -> eyre::Result<()> {
                             (sig, args),
                             eth.chain,
                             eth.etherscan_api_key,
+                            tx,
                             cast_async,
                         )
                         .await?;
@@ -198,6 +206,7 @@ async fn main() -> eyre::Result<()> {
                             (sig, args),
                             eth.chain,
                             eth.etherscan_api_key,
+                            tx,
                             cast_async,
                         )
                         .await?;
@@ -210,6 +219,7 @@ async fn main() -> eyre::Result<()> {
                             (sig, args),
                             eth.chain,
                             eth.etherscan_api_key,
+                            tx,
                             cast_async,
                         )
                         .await?;
@@ -224,19 +234,25 @@ async fn main() -> eyre::Result<()> {
                     (sig, args),
                     eth.chain,
                     eth.etherscan_api_key,
+                    tx,
                     cast_async,
                 )
                 .await?;
             }
         }
-        Subcommands::Estimate { eth, to, sig, args } => {
+        Subcommands::Estimate { eth, to, sig, args, cost } => {
             let provider = Provider::try_from(eth.rpc_url()?)?;
             let cast = Cast::new(&provider);
             let from = eth.sender().await;
-            let gas = cast
-                .estimate(from, to, Some((sig.as_str(), args)), eth.chain, eth.etherscan_api_key)
-                .await?;
-            println!("{}", gas);
+            let args = Some((sig.as_str(), args));
+            if cost {
+                let cost =
+                    cast.estimate_cost(from, to, args, eth.chain, eth.etherscan_api_key).await?;
+                println!("{}", cost);
+            } else {
+                let gas = cast.estimate(from, to, args, eth.chain, eth.etherscan_api_key).await?;
+                println!("{}", gas);
+            }
         }
         Subcommands::CalldataDecode { sig, calldata } => {
             let tokens = SimpleCast::abi_decode(&sig, &calldata, true)?;
@@ -548,6 +564,7 @@ async fn cast_send<M: Middleware, F: Into<NameOrAddress>, T: Into<NameOrAddress>
     args: (String, Vec<String>),
     chain: Chain,
     etherscan_api_key: Option<String>,
+    tx: TxOptions,
     cast_async: bool,
 ) -> eyre::Result<()>
 where
@@ -558,7 +575,7 @@ where
     let sig = args.0;
     let params = args.1;
     let params = if !sig.is_empty() { Some((&sig[..], params)) } else { None };
-    let pending_tx = cast.send(from, to, params, chain, etherscan_api_key).await?;
+    let pending_tx = cast.send_with(from, to, params, chain, etherscan_api_key, tx).await?;
     let tx_hash = *pending_tx;
 
     if cast_async {
@@ -566,6 +583,9 @@ where
     } else {
         let receipt = pending_tx.await?.ok_or_else(|| eyre::eyre!("tx {} not found", tx_hash))?;
         println!("Receipt: {:?}", receipt);
+        for (field, value) in cast.l1_receipt_fields(tx_hash).await? {
+            println!("{}: {}", field, value);
+        }
     }
 
     Ok(())
@@ -81,17 +81,57 @@ pub struct BuildArgs {
 
     #[clap(help = "add linked libraries", long, env = "DAPP_LIBRARIES")]
     pub libraries: Vec<String>,
+
+    #[clap(
+        help = "commands to run in the project root before compiling, e.g. to generate sources. Only files written to the contracts directory are compiled. A failing command aborts. Runs after the commands declared in hooks.txt",
+        long,
+        env = "FORGE_PRE_BUILD"
+    )]
+    pub pre_build: Vec<String>,
+
+    #[clap(
+        help = "commands to run in the project root after compiling. A failing command aborts. Runs after the commands declared in hooks.txt",
+        long,
+        env = "FORGE_POST_BUILD"
+    )]
+    pub post_build: Vec<String>,
+
+    #[clap(
+        help = "compile and test the project with each of the optimizer runs values and recommend the one with the best code size and gas tradeoff. Does not write any artifacts",
+        long,
+        value_name = "RUNS",
+        min_values = 0,
+        use_delimiter = true,
+        default_missing_values = &["1", "200", "1000", "10000", "100000"]
+    )]
+    pub profile_optimizer: Option<Vec<u32>>,
 }
 
 impl Cmd for BuildArgs {
-    type Output = ProjectCompileOutput<MinimalCombinedArtifacts>;
+    /// The compiler output, `None` when profiling the optimizer
+    type Output = Option<ProjectCompileOutput<MinimalCombinedArtifacts>>;
     fn run(self) -> eyre::Result<Self::Output> {
+        if let Some(ref runs) = self.profile_optimizer {
+            super::optimizer::profile(&self, runs)?;
+            return Ok(None)
+        }
         let project = self.project()?;
-        super::compile(&project)
+        self.compile(&project).map(Some)
     }
 }
 
 impl BuildArgs {
+    /// Compiles the project, running the build hooks around the compilation
+    pub fn compile(
+        &self,
+        project: &Project,
+    ) -> eyre::Result<ProjectCompileOutput<MinimalCombinedArtifacts>> {
+        utils::run_pre_build_hooks(project, &self.pre_build)?;
+        let output = super::compile(project)?;
+        utils::run_hooks("post_build", &self.post_build, &project.paths.root)?;
+        Ok(output)
+    }
+
     /// Determines the source directory within the given root
     fn contracts_path(&self, root: impl AsRef<Path>) -> PathBuf {
         let root = root.as_ref();
@@ -118,6 +158,13 @@ impl BuildArgs {
         }
     }
 
+    /// Determines the project's root, defaulting to the Git repository's root
+    pub fn project_root(&self) -> PathBuf {
+        self.root.clone().unwrap_or_else(|| {
+            utils::find_git_root_path().unwrap_or_else(|_| std::env::current_dir().unwrap())
+        })
+    }
+
     /// Determines the libraries
     fn libs(&self, root: impl AsRef<Path>) -> Vec<PathBuf> {
         let root = root.as_ref();
@@ -143,10 +190,7 @@ impl BuildArgs {
     /// Defaults to DAppTools-style repo layout, but can be customized.
     pub fn project(&self) -> eyre::Result<Project> {
         // 1. Set the root dir
-        let root = self.root.clone().unwrap_or_else(|| {
-            utils::find_git_root_path().unwrap_or_else(|_| std::env::current_dir().unwrap())
-        });
-        let root = dunce::canonicalize(&root)?;
+        let root = dunce::canonicalize(&self.project_root())?;
 
         // 2. Set the contracts dir
         let contracts = self.contracts_path(&root);
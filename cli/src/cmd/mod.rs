@@ -2,11 +2,15 @@
 
 pub mod build;
 pub mod create;
+pub mod optimizer;
 pub mod remappings;
+pub mod repl;
 pub mod run;
 pub mod snapshot;
 pub mod test;
+pub mod upgrade_check;
 pub mod verify;
+pub mod verify_bytecode;
 
 use crate::opts::forge::ContractInfo;
 use ethers::{
@@ -64,14 +68,23 @@ pub fn manual_compile(
     let mut sources = project.paths.read_input_files()?;
     sources.extend(Source::read_all_files(added_sources)?);
     println!("compiling...");
+    let output = compile_sources(project, sources)?;
+    if output.has_compiler_errors() {
+        // return the diagnostics error back to the user.
+        eyre::bail!(output.to_string())
+    }
+    Ok(output)
+}
+
+/// Compiles the given sources and the sources they import with the project's settings, compiler
+/// errors are returned as part of the output
+pub fn compile_sources(
+    project: &Project<MinimalCombinedArtifacts>,
+    sources: Sources,
+) -> eyre::Result<ProjectCompileOutput<MinimalCombinedArtifacts>> {
     if project.auto_detect {
         tracing::trace!("using solc auto detection to compile sources");
-        let output = project.svm_compile(sources)?;
-        if output.has_compiler_errors() {
-            // return the diagnostics error back to the user.
-            eyre::bail!(output.to_string())
-        }
-        return Ok(output)
+        return Ok(project.svm_compile(sources)?)
     }
 
     let mut solc = project.solc.clone();
@@ -80,12 +93,7 @@ pub fn manual_compile(
     }
 
     let sources = Graph::resolve_sources(&project.paths, sources)?.into_sources();
-    let output = project.compile_with_version(&solc, sources)?;
-    if output.has_compiler_errors() {
-        // return the diagnostics error back to the user.
-        eyre::bail!(output.to_string())
-    }
-    Ok(output)
+    Ok(project.compile_with_version(&solc, sources)?)
 }
 
 /// Given a project and its compiled artifacts, proceeds to return the ABI, Bytecode and
@@ -0,0 +1,225 @@
+//! Optimizer runs profiling, see `forge build --profile-optimizer`
+
+use crate::{
+    cmd::{build::BuildArgs, test::Filter},
+    utils,
+};
+use clap::Parser;
+use evm_adapters::evm_opts::EvmOpts;
+use forge::MultiContractRunnerBuilder;
+use proptest::test_runner::TestRunner;
+use std::fmt;
+
+/// Maximum runtime code size of a contract, as per EIP-170
+const CONTRACT_SIZE_LIMIT: usize = 24576;
+
+/// Tests whose gas usage is within this fraction of the best observed gas are considered equally
+/// good, in which case the smaller code wins
+const GAS_TOLERANCE: f64 = 0.01;
+
+/// The measured outcome of compiling and testing the project with a single runs value
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunsProfile {
+    /// The optimizer runs value
+    pub runs: u32,
+    /// Sum of the runtime code sizes of all non-test contracts
+    pub code_size: usize,
+    /// Size of the largest non-test contract
+    pub max_code_size: usize,
+    /// Sum of the gas used by all tests, using the median for fuzz tests
+    pub gas_used: u64,
+    /// Number of failed tests
+    pub failures: usize,
+}
+
+/// Compiles the project with every runs value, runs the test suite against each build and
+/// prints a comparison and a recommended runs value.
+///
+/// The build hooks run once around the whole profiling, the tests run with the default test
+/// options and the environment's `DAPP_*` settings.
+pub fn profile(build: &BuildArgs, runs: &[u32]) -> eyre::Result<Vec<RunsProfile>> {
+    if runs.is_empty() {
+        eyre::bail!("no optimizer runs values to profile")
+    }
+
+    let root = build.project_root();
+    utils::run_hooks("pre_build", &build.pre_build, &root)?;
+    let evm_opts = EvmOpts::parse_from(["forge"]);
+    let mut profiles = Vec::with_capacity(runs.len());
+    for runs in runs {
+        println!("profiling optimizer with {} runs", runs);
+        profiles.push(profile_runs(build, &evm_opts, *runs)?);
+    }
+    utils::run_hooks("post_build", &build.post_build, &root)?;
+
+    print_profiles(&profiles);
+    Ok(profiles)
+}
+
+fn profile_runs(build: &BuildArgs, evm_opts: &EvmOpts, runs: u32) -> eyre::Result<RunsProfile> {
+    let mut build = build.clone();
+    build.compiler.optimize = true;
+    build.compiler.optimize_runs = runs;
+
+    // compile in memory so that the profiled builds do not clobber the artifacts and cache
+    let mut project = build.project()?;
+    project.cached = false;
+    project.no_artifacts = true;
+
+    let mut evm_cfg = utils::sputnik_cfg(&build.compiler.evm_version);
+    evm_cfg.create_contract_limit = None;
+
+    // replay the exact same fuzz inputs for every build
+    let builder = MultiContractRunnerBuilder::default()
+        .fuzzer(TestRunner::deterministic())
+        .initial_balance(evm_opts.initial_balance)
+        .evm_cfg(evm_cfg)
+        .sender(evm_opts.sender);
+
+    let mut runner = builder.build(project, evm_opts.clone())?;
+    let results = runner.test(&Filter::parse_from(["forge"]))?;
+
+    let code_sizes = runner
+        .known_contracts
+        .values()
+        .filter(|(abi, _)| !abi.functions().any(|func| func.name.starts_with("test")))
+        .map(|(_, code)| code.len())
+        .collect::<Vec<_>>();
+
+    let tests = results.values().flat_map(|tests| tests.values());
+    let (gas_used, failures) = tests.fold((0u64, 0usize), |(gas, failures), result| {
+        (gas + result.kind.gas_used().gas(), failures + !result.success as usize)
+    });
+
+    Ok(RunsProfile {
+        runs,
+        code_size: code_sizes.iter().sum(),
+        max_code_size: code_sizes.iter().copied().max().unwrap_or_default(),
+        gas_used,
+        failures,
+    })
+}
+
+/// Why no runs value could be recommended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoRecommendation {
+    /// No runs values were profiled
+    NoProfiles,
+    /// Every build has a contract exceeding the contract size limit
+    SizeLimit,
+    /// Every build within the contract size limit fails more tests than the best build
+    Failures { min_failures: usize },
+}
+
+impl fmt::Display for NoRecommendation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoRecommendation::NoProfiles => write!(f, "no optimizer runs values were profiled"),
+            NoRecommendation::SizeLimit => write!(
+                f,
+                "no build stays within the contract size limit of {} bytes",
+                CONTRACT_SIZE_LIMIT
+            ),
+            NoRecommendation::Failures { min_failures } => write!(
+                f,
+                "every build within the contract size limit fails more tests than the best build, which fails {}",
+                min_failures
+            ),
+        }
+    }
+}
+
+/// Returns the recommended runs value among the profiles.
+///
+/// Builds with contracts exceeding the contract size limit or more failing tests than the best
+/// build are discarded. Of the remaining, the smallest runs value whose gas usage is within
+/// [`GAS_TOLERANCE`] of the lowest observed gas usage is picked, since higher runs values only
+/// grow the code from there.
+pub fn recommend(profiles: &[RunsProfile]) -> Result<u32, NoRecommendation> {
+    let min_failures =
+        profiles.iter().map(|p| p.failures).min().ok_or(NoRecommendation::NoProfiles)?;
+    let within_limit =
+        profiles.iter().filter(|p| p.max_code_size <= CONTRACT_SIZE_LIMIT).collect::<Vec<_>>();
+    if within_limit.is_empty() {
+        return Err(NoRecommendation::SizeLimit)
+    }
+    let mut candidates =
+        within_limit.into_iter().filter(|p| p.failures == min_failures).collect::<Vec<_>>();
+    let best_gas = candidates
+        .iter()
+        .map(|p| p.gas_used)
+        .min()
+        .ok_or(NoRecommendation::Failures { min_failures })?;
+    let threshold = best_gas as f64 * (1.0 + GAS_TOLERANCE);
+
+    candidates.sort_by_key(|p| p.runs);
+    Ok(candidates
+        .into_iter()
+        .find(|p| p.gas_used as f64 <= threshold)
+        .map(|p| p.runs)
+        .expect("the candidate with the best gas is within the tolerance"))
+}
+
+fn print_profiles(profiles: &[RunsProfile]) {
+    println!();
+    println!(
+        "{:>10} {:>14} {:>14} {:>16} {:>9}",
+        "runs", "code size", "largest", "test gas", "failures"
+    );
+    for profile in profiles {
+        println!(
+            "{:>10} {:>14} {:>14} {:>16} {:>9}",
+            profile.runs,
+            profile.code_size,
+            profile.max_code_size,
+            profile.gas_used,
+            profile.failures
+        );
+    }
+    println!();
+
+    match recommend(profiles) {
+        Ok(runs) => println!("Recommended optimizer runs: {}", runs),
+        Err(reason) => println!("No recommendation: {}", reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(runs: u32, max_code_size: usize, gas_used: u64, failures: usize) -> RunsProfile {
+        RunsProfile { runs, code_size: max_code_size, max_code_size, gas_used, failures }
+    }
+
+    #[test]
+    fn recommends_smallest_runs_within_gas_tolerance() {
+        let profiles = vec![
+            profile(1, 10_000, 1_200_000, 0),
+            profile(200, 11_000, 1_004_000, 0),
+            profile(10_000, 14_000, 1_000_000, 0),
+        ];
+        assert_eq!(recommend(&profiles), Ok(200));
+    }
+
+    #[test]
+    fn skips_oversized_and_failing_builds() {
+        let profiles = vec![
+            profile(1, 10_000, 1_200_000, 0),
+            profile(200, 11_000, 1_000_000, 1),
+            profile(10_000, 30_000, 900_000, 0),
+        ];
+        assert_eq!(recommend(&profiles), Ok(1));
+    }
+
+    #[test]
+    fn reports_why_nothing_is_recommended() {
+        assert_eq!(recommend(&[]), Err(NoRecommendation::NoProfiles));
+
+        let profiles = vec![profile(1, 30_000, 1_000_000, 0)];
+        assert_eq!(recommend(&profiles), Err(NoRecommendation::SizeLimit));
+
+        let profiles = vec![profile(1, 10_000, 1_000_000, 2), profile(200, 30_000, 900_000, 0)];
+        assert_eq!(recommend(&profiles), Err(NoRecommendation::Failures { min_failures: 0 }));
+    }
+}
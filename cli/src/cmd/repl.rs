@@ -0,0 +1,613 @@
+//! repl command
+
+use crate::cmd::{build::BuildArgs, compile, compile_sources, Cmd};
+use ansi_term::Colour;
+use clap::{Parser, ValueHint};
+use ethers::{
+    abi::{Abi, Function},
+    prelude::Artifact,
+    solc::{artifacts::Source, MinimalCombinedArtifacts, Project, ProjectCompileOutput},
+    types::{Bytes, U256},
+};
+use evm_adapters::{
+    evm_opts::{BackendKind, EvmOpts, EvmType},
+    signatures::SignatureCache,
+    sputnik::helpers::vm,
+};
+use forge::{ContractRunner, TestResult};
+use foundry_utils::{format_token, IntoFunction};
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+/// Name of the contract the session is compiled into
+const REPL_CONTRACT: &str = "Repl";
+
+/// Key of the log used to report the value of an expression back to the repl
+const RESULT_KEY: &str = "__repl_result__";
+
+/// Name of the getter returning the expression, whose return type is used to decode its value
+const RESULT_GETTER: &str = "__repl_value__";
+
+#[derive(Debug, Clone, Parser)]
+pub struct ReplArgs {
+    #[clap(flatten)]
+    pub evm_opts: EvmOpts,
+
+    #[clap(flatten)]
+    opts: BuildArgs,
+
+    #[clap(
+        help = "load a previously saved session",
+        long,
+        value_hint = ValueHint::FilePath
+    )]
+    pub load: Option<PathBuf>,
+}
+
+impl Cmd for ReplArgs {
+    type Output = ();
+    fn run(self) -> eyre::Result<Self::Output> {
+        #[cfg(not(feature = "sputnik-evm"))]
+        eyre::bail!("`repl` does not work with EVMs other than Sputnik yet");
+
+        if let EvmType::External(_) = self.evm_opts.evm_type {
+            eyre::bail!("`repl` does not support external EVMs, use `forge test` instead")
+        }
+
+        let mut repl = Repl::new(self.evm_opts, self.opts)?;
+        if let Some(path) = self.load {
+            repl.load(&path)?;
+        }
+
+        println!("Welcome to the forge repl, type `!help` for a list of commands");
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("> ");
+            io::stdout().flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+            match repl.eval(&line) {
+                Ok(ReplAction::Continue) => {}
+                Ok(ReplAction::Exit) => return Ok(()),
+                Err(err) => println!("{}", Colour::Red.paint(err.to_string())),
+            }
+        }
+    }
+}
+
+/// What the repl should do after evaluating an input
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReplAction {
+    Continue,
+    Exit,
+}
+
+/// The inputs of a repl session, from which the session contract is generated.
+///
+/// The session is stateless in between inputs: every input is evaluated by recompiling the
+/// session and replaying all of its statements in a fresh EVM, so that a session can be
+/// restored from just its inputs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    /// `import` directives
+    imports: Vec<String>,
+    /// Source unit level definitions, i.e. contracts, interfaces, libraries, structs, ...
+    definitions: Vec<String>,
+    /// Members of the session contract, i.e. events and errors
+    members: Vec<String>,
+    /// Statements executed in order in the body of the session
+    statements: Vec<String>,
+}
+
+/// Classification of a single line of input
+#[derive(Debug, Clone, PartialEq)]
+enum Input<'a> {
+    Import(&'a str),
+    Definition(&'a str),
+    Member(&'a str),
+    Statement(&'a str),
+    Expression(&'a str),
+}
+
+impl<'a> Input<'a> {
+    fn parse(input: &'a str) -> Self {
+        let input = input.trim();
+        let keyword = input.split_whitespace().next().unwrap_or_default();
+        match keyword {
+            "import" => Input::Import(input),
+            "pragma" | "contract" | "abstract" | "interface" | "library" | "struct" | "enum" |
+            "function" | "type" => Input::Definition(input),
+            "event" | "error" => Input::Member(input),
+            _ if input.ends_with(';') || input.ends_with('}') => Input::Statement(input),
+            _ => Input::Expression(input),
+        }
+    }
+}
+
+impl Session {
+    /// Adds the input to the session, returns the expression to evaluate if the input is one
+    fn push<'a>(&mut self, input: &'a str) -> Option<&'a str> {
+        match Input::parse(input) {
+            Input::Import(s) => self.imports.push(s.to_string()),
+            Input::Definition(s) => self.definitions.push(s.to_string()),
+            Input::Member(s) => self.members.push(s.to_string()),
+            Input::Statement(s) => self.statements.push(s.to_string()),
+            Input::Expression(expr) => return Some(expr),
+        }
+        None
+    }
+
+    /// Generates the source of the session contract, reporting the value of `expr` if provided.
+    ///
+    /// If the type of the expression is provided as well, a getter returning the expression is
+    /// added, so that the abi of the contract describes how to decode the reported value.
+    pub fn source(&self, expr: Option<&str>, ty: Option<&str>) -> String {
+        let mut source = String::from("// SPDX-License-Identifier: UNLICENSED\n");
+        source.push_str("pragma solidity >=0.6.2;\n\n");
+        self.imports.iter().for_each(|s| source.push_str(&format!("{}\n", s)));
+        self.definitions.iter().for_each(|s| source.push_str(&format!("{}\n", s)));
+
+        source.push_str(&format!("\ncontract {} {{\n", REPL_CONTRACT));
+        source.push_str("    event log_named_bytes(string key, bytes val);\n");
+        self.members.iter().for_each(|s| source.push_str(&format!("    {}\n", s)));
+
+        source.push_str("\n    function run() public {\n");
+        self.statements.iter().for_each(|s| source.push_str(&format!("        {}\n", s)));
+        if let Some(expr) = expr {
+            source.push_str(&format!(
+                "        emit log_named_bytes(\"{}\", abi.encode({}));\n",
+                RESULT_KEY, expr
+            ));
+        }
+        source.push_str("    }\n");
+
+        if let (Some(expr), Some(ty)) = (expr, ty) {
+            source.push_str(&format!(
+                "\n    function {}() public returns ({}) {{\n",
+                RESULT_GETTER, ty
+            ));
+            self.statements.iter().for_each(|s| source.push_str(&format!("        {}\n", s)));
+            source.push_str(&format!("        return {};\n    }}\n", expr));
+        }
+        source.push_str("}\n");
+        source
+    }
+
+    /// Returns all inputs of the session, one per line, in a format that can be loaded again
+    pub fn to_script(&self) -> String {
+        self.imports
+            .iter()
+            .chain(&self.definitions)
+            .chain(&self.members)
+            .chain(&self.statements)
+            .map(|s| format!("{}\n", s))
+            .collect()
+    }
+
+    /// Restores a session from the inputs of a saved session
+    pub fn from_script(script: &str) -> Self {
+        let mut session = Session::default();
+        script.lines().filter(|line| !line.trim().is_empty()).for_each(|line| {
+            session.push(line);
+        });
+        session
+    }
+
+    /// Returns true if nothing was added to the session yet
+    pub fn is_empty(&self) -> bool {
+        self.to_script().is_empty()
+    }
+}
+
+struct Repl {
+    evm_opts: EvmOpts,
+    opts: BuildArgs,
+    session: Session,
+    /// The project the session is compiled against
+    project: Project<MinimalCombinedArtifacts>,
+    /// Runtime code of the project's contracts, compiled once when the repl starts
+    known_contracts: BTreeMap<String, (Abi, Vec<u8>)>,
+    signatures: SignatureCache,
+    /// Where the session contract is written to for compilation, inside a directory owned by the
+    /// repl which is removed when it exits
+    file: PathBuf,
+    traces: bool,
+}
+
+impl Drop for Repl {
+    fn drop(&mut self) {
+        if let Some(dir) = self.file.parent() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+impl Repl {
+    fn new(evm_opts: EvmOpts, opts: BuildArgs) -> eyre::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("forge-repl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join(format!("{}.sol", REPL_CONTRACT));
+
+        // the project is only compiled once, every input then only compiles the session contract
+        // and the sources it imports
+        let project_root = opts.project_root();
+        crate::utils::run_hooks("pre_build", &opts.pre_build, &project_root)?;
+        let mut project = opts.project()?;
+        project.cached = false;
+        project.no_artifacts = true;
+        let known_contracts = if project.paths.sources.exists() {
+            runtime_codes(contracts(compile(&project)?))
+        } else {
+            BTreeMap::new()
+        };
+        crate::utils::run_hooks("post_build", &opts.post_build, &project_root)?;
+
+        let mut signatures = SignatureCache::default();
+        known_contracts.values().for_each(|(abi, _)| signatures.extend_from_abi(abi));
+
+        Ok(Self {
+            evm_opts,
+            opts,
+            session: Session::default(),
+            project,
+            known_contracts,
+            signatures,
+            file,
+            traces: false,
+        })
+    }
+
+    fn eval(&mut self, line: &str) -> eyre::Result<ReplAction> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(ReplAction::Continue)
+        }
+        if let Some(cmd) = line.strip_prefix('!') {
+            return self.command(cmd)
+        }
+
+        // only keep the input if the session still compiles and executes with it
+        let mut session = self.session.clone();
+        let expr = session.push(line);
+        let (result, getter) = self.execute(&session, expr)?;
+        self.print_result(&result, getter.as_ref());
+        if result.success {
+            self.session = session;
+        }
+        Ok(ReplAction::Continue)
+    }
+
+    fn command(&mut self, cmd: &str) -> eyre::Result<ReplAction> {
+        let mut args = cmd.split_whitespace();
+        match args.next().unwrap_or_default() {
+            "help" | "h" => {
+                println!("Statements ending with `;` are added to the session,");
+                println!("anything else is evaluated as an expression and its value printed.");
+                println!();
+                println!("!help               print this message");
+                println!("!source             print the source of the session");
+                println!("!clear              reset the session");
+                println!("!traces             toggle printing the traces of every input");
+                println!("!save <path>        save the session to a file");
+                println!("!load <path>        load a session from a file");
+                println!("!exit               exit the repl");
+            }
+            "source" => println!("{}", self.session.source(None, None)),
+            "clear" => {
+                self.session = Session::default();
+                println!("session cleared");
+            }
+            "traces" => {
+                self.traces = !self.traces;
+                println!("traces {}", if self.traces { "enabled" } else { "disabled" });
+            }
+            "save" => {
+                let path = args.next().ok_or_else(|| eyre::eyre!("usage: !save <path>"))?;
+                std::fs::write(path, self.session.to_script())?;
+                println!("session saved to {}", path);
+            }
+            "load" => {
+                let path = args.next().ok_or_else(|| eyre::eyre!("usage: !load <path>"))?;
+                self.load(Path::new(path))?;
+            }
+            "exit" | "quit" | "q" => return Ok(ReplAction::Exit),
+            other => eyre::bail!("unknown command `!{}`, see `!help`", other),
+        }
+        Ok(ReplAction::Continue)
+    }
+
+    /// Replaces the current session with the one saved at `path`
+    fn load(&mut self, path: &Path) -> eyre::Result<()> {
+        let session = Session::from_script(&std::fs::read_to_string(path)?);
+        if !session.is_empty() {
+            let (result, _) = self.execute(&session, None)?;
+            if !result.success {
+                self.print_result(&result, None);
+                eyre::bail!("failed to replay session {}", path.display())
+            }
+        }
+        self.session = session;
+        println!("session loaded from {}", path.display());
+        Ok(())
+    }
+
+    /// Compiles the session contract with the given source and the sources it imports
+    fn compile(
+        &self,
+        source: &str,
+    ) -> eyre::Result<ProjectCompileOutput<MinimalCombinedArtifacts>> {
+        std::fs::write(&self.file, source)?;
+        compile_sources(&self.project, Source::read_all_files(vec![self.file.clone()])?)
+    }
+
+    /// Returns the type of the expression as it can be declared as a return type, by letting the
+    /// compiler report it for a getter declared to return something else. Returns `None` if the
+    /// type can't be returned, e.g. for tuples
+    fn expression_type(&self, session: &Session, expr: &str) -> eyre::Result<Option<String>> {
+        let output = self.compile(&session.source(Some(expr), Some("bool")))?;
+        if !output.has_compiler_errors() {
+            return Ok(Some("bool".to_string()))
+        }
+        Ok(reported_type(&output.to_string()).and_then(|ty| return_type(&ty)))
+    }
+
+    /// Compiles the session and executes it in a fresh EVM, returns the getter describing the
+    /// value of the expression if it could be generated
+    fn execute(
+        &self,
+        session: &Session,
+        expr: Option<&str>,
+    ) -> eyre::Result<(TestResult, Option<Function>)> {
+        let ty = match expr {
+            Some(expr) => self.expression_type(session, expr)?,
+            None => None,
+        };
+        let output = self.compile(&session.source(expr, ty.as_deref()))?;
+        if output.has_compiler_errors() {
+            eyre::bail!(output.to_string())
+        }
+        let mut contracts = contracts(output);
+        let (abi, bytecode, _) = contracts
+            .remove(REPL_CONTRACT)
+            .ok_or_else(|| eyre::eyre!("session contract not found in the compiler output"))?;
+        let getter = abi.function(RESULT_GETTER).ok().cloned();
+
+        let mut known_contracts = self.known_contracts.clone();
+        let mut signatures = self.signatures.clone();
+        contracts.values().for_each(|(abi, _, _)| signatures.extend_from_abi(abi));
+        signatures.extend_from_abi(&abi);
+        known_contracts.extend(runtime_codes(contracts));
+
+        let mut evm_opts = self.evm_opts.clone();
+        if self.traces {
+            evm_opts.verbosity = evm_opts.verbosity.max(4);
+        }
+        let func = IntoFunction::into("run()");
+
+        let cfg = crate::utils::sputnik_cfg(&self.opts.compiler.evm_version);
+        let vicinity = evm_opts.vicinity()?;
+        let backend = evm_opts.backend(&vicinity)?;
+
+        let result = match backend {
+            BackendKind::Simple(ref backend) => {
+                let runner = ContractRunner::new(
+                    &evm_opts,
+                    &cfg,
+                    backend,
+                    &abi,
+                    bytecode,
+                    Some(evm_opts.sender),
+                    None,
+                );
+                runner.run_test(&func, false, Some(&known_contracts))?
+            }
+            BackendKind::Shared(ref backend) => {
+                let runner = ContractRunner::new(
+                    &evm_opts,
+                    &cfg,
+                    backend,
+                    &abi,
+                    bytecode,
+                    Some(evm_opts.sender),
+                    None,
+                );
+                runner.run_test(&func, false, Some(&known_contracts))?
+            }
+        };
+
+        if self.traces {
+            if let (Some(traces), Some(identified_contracts)) =
+                (&result.traces, &result.identified_contracts)
+            {
+                let mut ident = identified_contracts.clone();
+                if let Some(trace) = traces.last() {
+                    trace.pretty_print(0, &known_contracts, &signatures, &mut ident, &vm(), "");
+                    println!();
+                }
+            }
+        }
+
+        Ok((result, getter))
+    }
+
+    fn print_result(&self, result: &TestResult, getter: Option<&Function>) {
+        let prefix = format!("{}: ", RESULT_KEY);
+        for log in &result.logs {
+            match log.strip_prefix(&prefix) {
+                Some(encoded) => print_encoded(encoded, getter),
+                None => println!("{}", log),
+            }
+        }
+        if !result.success {
+            println!(
+                "{}",
+                Colour::Red.paint(format!(
+                    "execution reverted{}",
+                    result.reason.as_ref().map(|r| format!(": {}", r)).unwrap_or_default()
+                ))
+            );
+        }
+    }
+}
+
+/// The abi, creation and runtime code of the compiled contracts by name
+fn contracts(
+    output: ProjectCompileOutput<MinimalCombinedArtifacts>,
+) -> BTreeMap<String, (Abi, Bytes, Bytes)> {
+    output
+        .into_artifacts()
+        .filter_map(|(name, artifact)| {
+            let name = name.rsplit(':').next().unwrap_or_default().to_string();
+            match artifact.into_parts() {
+                (Some(abi), Some(bytecode), Some(runtime)) => {
+                    Some((name, (abi, bytecode, runtime)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn runtime_codes(
+    contracts: BTreeMap<String, (Abi, Bytes, Bytes)>,
+) -> BTreeMap<String, (Abi, Vec<u8>)> {
+    contracts.into_iter().map(|(name, (abi, _, runtime))| (name, (abi, runtime.to_vec()))).collect()
+}
+
+/// Extracts the type of the returned expression from the compiler error of the type probe
+fn reported_type(errors: &str) -> Option<String> {
+    let marker = "Return argument type ";
+    let start = errors.find(marker)? + marker.len();
+    let end = errors[start..].find(" is not implicitly convertible")?;
+    Some(errors[start..start + end].to_string())
+}
+
+/// Turns a type as reported by the compiler into a type that can be declared as return type,
+/// e.g. `int_const 42` into `uint256` and `struct S storage ref` into `S memory`
+fn return_type(ty: &str) -> Option<String> {
+    let ty = ty.trim();
+    if let Some(value) = ty.strip_prefix("int_const ") {
+        return Some(if value.starts_with('-') { "int256" } else { "uint256" }.to_string())
+    }
+    if ty.starts_with("literal_string") {
+        return Some("string memory".to_string())
+    }
+    if ["tuple(", "rational_const", "type(", "function ", "mapping(", "literal_"]
+        .iter()
+        .any(|prefix| ty.starts_with(prefix))
+    {
+        return None
+    }
+
+    let reference = [" storage", " memory", " calldata"].iter().any(|loc| ty.contains(loc));
+    let mut ty = ty.to_string();
+    for noise in [
+        "struct ",
+        "contract ",
+        "enum ",
+        " storage ref",
+        " storage pointer",
+        " calldata",
+        " memory",
+    ] {
+        ty = ty.replace(noise, "");
+    }
+    Some(if reference { format!("{} memory", ty) } else { ty })
+}
+
+/// Prints the abi encoded value of an expression, decoded with the return type of the getter if
+/// there is one
+fn print_encoded(encoded: &str, getter: Option<&Function>) {
+    let bytes = match hex::decode(encoded.trim_start_matches("0x")) {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            println!("{}", encoded);
+            return
+        }
+    };
+    if let Some(getter) = getter {
+        if let Ok(tokens) = getter.decode_output(&bytes) {
+            for (param, token) in getter.outputs.iter().zip(&tokens) {
+                println!("{}: {}", param.kind, format_token(token));
+            }
+            return
+        }
+    }
+    if bytes.len() == 32 {
+        println!("Hex: 0x{}", hex::encode(&bytes));
+        println!("Decimal: {}", U256::from_big_endian(&bytes));
+    } else {
+        for (i, word) in bytes.chunks(32).enumerate() {
+            println!("[{:#04x}]: 0x{}", i * 32, hex::encode(word));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_classify_inputs() {
+        assert_eq!(Input::parse("import \"./A.sol\";"), Input::Import("import \"./A.sol\";"));
+        assert_eq!(
+            Input::parse("interface IERC20 { function totalSupply() external returns (uint); }"),
+            Input::Definition(
+                "interface IERC20 { function totalSupply() external returns (uint); }"
+            )
+        );
+        assert_eq!(Input::parse("event Foo(uint a);"), Input::Member("event Foo(uint a);"));
+        assert_eq!(Input::parse(" uint a = 1; "), Input::Statement("uint a = 1;"));
+        assert_eq!(Input::parse("if (a > 0) { a--; }"), Input::Statement("if (a > 0) { a--; }"));
+        assert_eq!(Input::parse("a + 1"), Input::Expression("a + 1"));
+    }
+
+    #[test]
+    fn can_save_and_load_session() {
+        let mut session = Session::default();
+        assert!(session.push("uint a = 1;").is_none());
+        assert!(session.push("struct S { uint x; }").is_none());
+        assert_eq!(session.push("a * 2"), Some("a * 2"));
+
+        let source = session.source(Some("a * 2"), None);
+        assert!(source.contains("struct S { uint x; }\n\ncontract Repl {"));
+        assert!(source.contains("        uint a = 1;\n"));
+        assert!(source.contains("abi.encode(a * 2)"));
+        assert!(!source.contains(RESULT_GETTER));
+
+        let source = session.source(Some("a * 2"), Some("uint256"));
+        assert!(
+            source.contains(&format!("function {}() public returns (uint256) {{", RESULT_GETTER))
+        );
+        assert!(source.contains("        return a * 2;\n"));
+
+        let restored = Session::from_script(&session.to_script());
+        assert_eq!(restored, session);
+        assert_eq!(restored.source(None, None), session.source(None, None));
+    }
+
+    #[test]
+    fn can_derive_return_types() {
+        let errors = "TypeError: Return argument type int_const 2 is not implicitly convertible to expected type (type of first return variable) bool.";
+        assert_eq!(reported_type(errors).as_deref(), Some("int_const 2"));
+        assert_eq!(reported_type("ParserError: Expected ';'"), None);
+
+        assert_eq!(return_type("int_const 2").as_deref(), Some("uint256"));
+        assert_eq!(return_type("int_const -1").as_deref(), Some("int256"));
+        assert_eq!(return_type("uint8").as_deref(), Some("uint8"));
+        assert_eq!(return_type("address payable").as_deref(), Some("address payable"));
+        assert_eq!(return_type("literal_string \"abc\"").as_deref(), Some("string memory"));
+        assert_eq!(return_type("contract IERC20").as_deref(), Some("IERC20"));
+        assert_eq!(return_type("enum Repl.Kind").as_deref(), Some("Repl.Kind"));
+        assert_eq!(return_type("struct S memory").as_deref(), Some("S memory"));
+        assert_eq!(return_type("uint256[] storage ref").as_deref(), Some("uint256[] memory"));
+        assert_eq!(return_type("struct S memory[] memory").as_deref(), Some("S[] memory"));
+        assert_eq!(return_type("tuple(uint256,bool)"), None);
+    }
+}
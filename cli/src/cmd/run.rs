@@ -1,7 +1,7 @@
 use crate::cmd::{build::BuildArgs, compile, manual_compile, Cmd};
 use clap::{Parser, ValueHint};
 use ethers::abi::Abi;
-use forge::ContractRunner;
+use forge::{ContractRunner, StorageLayout};
 use foundry_utils::IntoFunction;
 use std::{collections::BTreeMap, path::PathBuf};
 use ui::{TUIExitReason, Tui, Ui};
@@ -17,7 +17,8 @@ use ethers::{
     solc::artifacts::{CompactContractSome, ContractBytecodeSome},
 };
 use evm_adapters::{
-    evm_opts::{BackendKind, EvmOpts},
+    evm_opts::{BackendKind, EvmOpts, EvmType},
+    signatures::SignatureCache,
     sputnik::{cheatcodes::debugger::DebugArena, helpers::vm},
 };
 
@@ -30,7 +31,7 @@ pub struct RunArgs {
     pub evm_opts: EvmOpts,
 
     #[clap(flatten)]
-    opts: BuildArgs,
+    pub opts: BuildArgs,
 
     #[clap(
         long,
@@ -54,13 +55,25 @@ impl Cmd for RunArgs {
         #[cfg(not(feature = "sputnik-evm"))]
         unimplemented!("`run` does not work with EVMs other than Sputnik yet");
 
+        // scripts rely on traces and the debugger, which external environments do not provide
+        if let EvmType::External(_) = self.evm_opts.evm_type {
+            eyre::bail!("`run` does not support external EVMs, use `forge test` instead")
+        }
+
         let mut evm_opts = self.evm_opts.clone();
         if evm_opts.debug {
             evm_opts.verbosity = 3;
         }
 
         let func = IntoFunction::into(self.sig.as_deref().unwrap_or("run()"));
-        let BuildOutput { project, contract, highlevel_known_contracts, sources } = self.build()?;
+        let BuildOutput {
+            project,
+            contract,
+            highlevel_known_contracts,
+            sources,
+            storage_layouts,
+            signatures,
+        } = self.build()?;
 
         let known_contracts = highlevel_known_contracts
             .iter()
@@ -94,6 +107,7 @@ impl Cmd for RunArgs {
                     &abi,
                     bytecode,
                     Some(evm_opts.sender),
+                    None,
                 );
                 runner.run_test(&func, needs_setup, Some(&known_contracts))?
             }
@@ -105,11 +119,23 @@ impl Cmd for RunArgs {
                     &abi,
                     bytecode,
                     Some(evm_opts.sender),
+                    None,
                 );
                 runner.run_test(&func, needs_setup, Some(&known_contracts))?
             }
         };
 
+        if let Some(ref state_diff) = result.state_diff {
+            crate::utils::print_state_diff(state_diff, &storage_layouts);
+        }
+
+        if let (Some(format), Some(trace)) =
+            (evm_opts.trace_format, result.traces.as_ref().and_then(|t| t.last()))
+        {
+            let trace = trace.export(format, evm_opts.sender);
+            println!("{}", serde_json::to_string_pretty(&trace)?);
+        }
+
         if evm_opts.debug {
             // 4. Boot up debugger
             let source_code: BTreeMap<u32, String> = sources
@@ -160,15 +186,32 @@ impl Cmd for RunArgs {
             {
                 if !result.success && evm_opts.verbosity == 3 || evm_opts.verbosity > 3 {
                     let mut ident = identified_contracts.clone();
+                    if evm_opts.etherscan_identify {
+                        crate::utils::etherscan_identify(
+                            &tokio::runtime::Runtime::new()?,
+                            &evm_opts,
+                            &project.paths.cache.with_file_name("etherscan"),
+                            traces,
+                            &mut ident,
+                        );
+                    }
                     if evm_opts.verbosity > 4 || !result.success {
                         // print setup calls as well
                         traces.iter().for_each(|trace| {
-                            trace.pretty_print(0, &known_contracts, &mut ident, &vm(), "");
+                            trace.pretty_print(
+                                0,
+                                &known_contracts,
+                                &signatures,
+                                &mut ident,
+                                &vm(),
+                                "",
+                            );
                         });
                     } else if !traces.is_empty() {
                         traces.last().expect("no last but not empty").pretty_print(
                             0,
                             &known_contracts,
+                            &signatures,
                             &mut ident,
                             &vm(),
                             "",
@@ -200,6 +243,10 @@ pub struct BuildOutput {
     pub contract: CompactContractSome,
     pub highlevel_known_contracts: BTreeMap<String, ContractBytecodeSome>,
     pub sources: BTreeMap<u32, String>,
+    /// Storage layouts of the compiled contracts, only collected with `--state-diff`
+    pub storage_layouts: BTreeMap<String, StorageLayout>,
+    /// Index of the functions and events of all compiled contracts, used to decode traces
+    pub signatures: SignatureCache,
 }
 
 impl RunArgs {
@@ -230,17 +277,26 @@ impl RunArgs {
         if self.opts.no_auto_detect {
             builder = builder.no_auto_detect();
         }
-        Ok(builder.build()?)
+        let mut project = builder.build()?;
+        if self.evm_opts.state_diff {
+            StorageLayout::request(&mut project);
+        }
+        Ok(project)
     }
 
     /// Compiles the file with auto-detection and compiler params.
     pub fn build(&self) -> eyre::Result<BuildOutput> {
         let root = dunce::canonicalize(&self.path)?;
+        let project_root = self.opts.project_root();
+        crate::utils::run_hooks("pre_build", &self.opts.pre_build, &project_root)?;
         let (project, output) = if let Ok(mut project) = self.opts.project() {
             // TODO: caching causes no output until https://github.com/gakonst/ethers-rs/issues/727
             // is fixed
             project.cached = false;
             project.no_artifacts = true;
+            if self.evm_opts.state_diff {
+                StorageLayout::request(&mut project);
+            }
             // target contract may not be in the compilation path, add it and manually compile
             match manual_compile(&project, vec![root.clone()]) {
                 Ok(output) => (project, output),
@@ -261,6 +317,7 @@ impl RunArgs {
             (target_project, res)
         };
         println!("success.");
+        crate::utils::run_hooks("post_build", &self.opts.post_build, &project_root)?;
 
         // get the contracts
         let (sources, contracts) = output.output().split();
@@ -304,6 +361,13 @@ impl RunArgs {
 
         let contract = contract_bytecode.into_compact_contract().unwrap();
 
+        let storage_layouts = contracts
+            .0
+            .values()
+            .flat_map(|mapping| mapping.iter())
+            .filter_map(|(name, c)| StorageLayout::from_contract(c).map(|l| (name.clone(), l)))
+            .collect();
+
         let mut highlevel_known_contracts = BTreeMap::new();
 
         // build the entire highlevel_known_contracts based on all compiled contracts
@@ -320,11 +384,16 @@ impl RunArgs {
             });
         });
 
+        let mut signatures = SignatureCache::default();
+        highlevel_known_contracts.values().for_each(|c| signatures.extend_from_abi(&c.abi));
+
         Ok(BuildOutput {
             project,
             contract,
             highlevel_known_contracts,
             sources: sources.into_ids().collect(),
+            storage_layouts,
+            signatures,
         })
     }
 }
@@ -81,6 +81,9 @@ pub struct TestArgs {
     #[clap(help = "print the test results in json format", long, short)]
     json: bool,
 
+    #[clap(help = "list the tests matching the filters instead of running them", long)]
+    list: bool,
+
     #[clap(flatten)]
     evm_opts: EvmOpts,
 
@@ -96,13 +99,20 @@ pub struct TestArgs {
         env = "FORGE_ALLOW_FAILURE"
     )]
     allow_failure: bool,
+
+    #[clap(
+        help = "commands to run in the project root after compiling, before the tests. A failing command aborts. Runs after the commands declared in hooks.txt",
+        long,
+        env = "FORGE_PRE_TEST"
+    )]
+    pre_test: Vec<String>,
 }
 
 impl Cmd for TestArgs {
     type Output = TestOutcome;
 
     fn run(self) -> eyre::Result<Self::Output> {
-        let TestArgs { opts, evm_opts, json, filter, allow_failure } = self;
+        let TestArgs { opts, evm_opts, json, list, filter, allow_failure, pre_test } = self;
         // Setup the fuzzer
         // TODO: Add CLI Options to modify the persistence
         let cfg = proptest::test_runner::Config { failure_persistence: None, ..Default::default() };
@@ -110,6 +120,9 @@ impl Cmd for TestArgs {
 
         // Set up the project
         let project = opts.project()?;
+        let root = project.paths.root.clone();
+        crate::utils::run_pre_build_hooks(&project, &opts.pre_build)?;
+        let post_build = || crate::utils::run_hooks("post_build", &opts.post_build, &root);
 
         // prepare the test builder
         let mut evm_cfg = crate::utils::sputnik_cfg(&opts.compiler.evm_version);
@@ -121,7 +134,15 @@ impl Cmd for TestArgs {
             .evm_cfg(evm_cfg)
             .sender(evm_opts.sender);
 
-        test(builder, project, evm_opts, filter, json, allow_failure)
+        if list {
+            return list_tests(builder, project, post_build, evm_opts, filter, json, allow_failure)
+        }
+
+        let before_test = || {
+            post_build()?;
+            crate::utils::run_hooks("pre_test", &pre_test, &root)
+        };
+        test(builder, project, before_test, evm_opts, filter, json, allow_failure)
     }
 }
 
@@ -195,17 +216,50 @@ impl TestOutcome {
     }
 }
 
+/// Lists all the tests matching the filter without running them
+fn list_tests<A: ArtifactOutput + 'static>(
+    builder: MultiContractRunnerBuilder,
+    project: Project<A>,
+    after_build: impl FnOnce() -> eyre::Result<()>,
+    evm_opts: EvmOpts,
+    filter: Filter,
+    json: bool,
+    allow_failure: bool,
+) -> eyre::Result<TestOutcome> {
+    let runner = builder.build(project, evm_opts)?;
+    after_build()?;
+    let tests = runner.list(&filter);
+
+    if json {
+        println!("{}", serde_json::to_string(&tests)?);
+    } else {
+        for (contract_name, tests) in tests {
+            println!("{}", contract_name);
+            for test in tests {
+                println!("  {}", test);
+            }
+        }
+    }
+
+    Ok(TestOutcome::new(BTreeMap::new(), allow_failure))
+}
+
 /// Runs all the tests
 fn test<A: ArtifactOutput + 'static>(
     builder: MultiContractRunnerBuilder,
     project: Project<A>,
+    after_build: impl FnOnce() -> eyre::Result<()>,
     evm_opts: EvmOpts,
     filter: Filter,
     json: bool,
     allow_failure: bool,
 ) -> eyre::Result<TestOutcome> {
     let verbosity = evm_opts.verbosity;
+    let etherscan_cache = project.paths.cache.with_file_name("etherscan");
+    let etherscan_rt =
+        if evm_opts.etherscan_identify { Some(tokio::runtime::Runtime::new()?) } else { None };
     let mut runner = builder.build(project, evm_opts)?;
+    after_build()?;
 
     let results = runner.test(&filter)?;
 
@@ -247,6 +301,17 @@ fn test<A: ArtifactOutput + 'static>(
                 };
 
                 println!("{} {} {}", status, name, result.kind.gas_used());
+
+                if let Some(ref state_diff) = result.state_diff {
+                    crate::utils::print_state_diff(state_diff, &runner.storage_layouts);
+                }
+
+                if let (Some(format), Some(trace)) =
+                    (runner.evm_opts.trace_format, result.traces.as_ref().and_then(|t| t.last()))
+                {
+                    let trace = trace.export(format, runner.evm_opts.sender);
+                    println!("{}", serde_json::to_string_pretty(&trace)?);
+                }
             }
 
             if verbosity > 1 {
@@ -269,12 +334,22 @@ fn test<A: ArtifactOutput + 'static>(
                         {
                             if !result.success && verbosity == 3 || verbosity > 3 {
                                 let mut ident = identified_contracts.clone();
+                                if let Some(ref rt) = etherscan_rt {
+                                    crate::utils::etherscan_identify(
+                                        rt,
+                                        &runner.evm_opts,
+                                        &etherscan_cache,
+                                        traces,
+                                        &mut ident,
+                                    );
+                                }
                                 if verbosity > 4 || !result.success {
                                     // print setup calls as well
                                     traces.iter().for_each(|trace| {
                                         trace.pretty_print(
                                             0,
                                             &runner.known_contracts,
+                                            &runner.signatures,
                                             &mut ident,
                                             &vm(),
                                             "",
@@ -284,6 +359,7 @@ fn test<A: ArtifactOutput + 'static>(
                                     traces.last().expect("no last but not empty").pretty_print(
                                         0,
                                         &runner.known_contracts,
+                                        &runner.signatures,
                                         &mut ident,
                                         &vm(),
                                         "",
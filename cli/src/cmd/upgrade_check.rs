@@ -0,0 +1,57 @@
+//! upgrade-check command
+
+use crate::cmd::Cmd;
+use ansi_term::Colour;
+use clap::{Parser, ValueHint};
+use forge::StorageLayout;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Parser)]
+pub struct UpgradeCheckArgs {
+    #[clap(
+        help = "the storage layout of the current implementation. Either a storage layout, an artifact with a `storageLayout` field or solc's standard json output",
+        value_hint = ValueHint::FilePath
+    )]
+    pub old: PathBuf,
+
+    #[clap(
+        help = "the storage layout of the new implementation, in any of the formats accepted for the old one",
+        value_hint = ValueHint::FilePath
+    )]
+    pub new: PathBuf,
+
+    #[clap(help = "the name of the old contract, required for standard json output", long)]
+    pub old_contract: Option<String>,
+
+    #[clap(help = "the name of the new contract, required for standard json output", long)]
+    pub new_contract: Option<String>,
+}
+
+impl Cmd for UpgradeCheckArgs {
+    type Output = ();
+    fn run(self) -> eyre::Result<Self::Output> {
+        let old = StorageLayout::load(&self.old, self.old_contract.as_deref())?;
+        let new = StorageLayout::load(&self.new, self.new_contract.as_deref())?;
+
+        let changes = old.diff(&new)?;
+        if changes.is_empty() {
+            println!("{}", Colour::Green.paint("Storage layouts are identical."));
+            return Ok(())
+        }
+
+        for change in &changes {
+            if change.is_breaking() {
+                println!("{} {}", Colour::Red.paint("[BREAKING]"), change);
+            } else {
+                println!("{} {}", Colour::Yellow.paint("[OK]"), change);
+            }
+        }
+
+        let breaking = changes.iter().filter(|change| change.is_breaking()).count();
+        if breaking > 0 {
+            eyre::bail!("Found {} storage layout incompatibilities", breaking)
+        }
+        println!("{}", Colour::Green.paint("Storage layouts are compatible."));
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! verify-bytecode command
+
+use crate::{
+    cmd::{build::BuildArgs, Cmd},
+    opts::forge::ContractInfo,
+};
+use ansi_term::Colour;
+use clap::Parser;
+use ethers::{
+    abi::{Abi, ParamType},
+    providers::{Http, Middleware, Provider},
+    types::{Address, H256},
+};
+use forge::{compare_creation, compare_runtime, BytecodeMatch};
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Parser)]
+pub struct VerifyBytecodeArgs {
+    #[clap(help = "the address of the deployed contract")]
+    pub address: Address,
+
+    #[clap(help = "contract source info `<path>:<contractname>` or `<contractname>`")]
+    pub contract: ContractInfo,
+
+    #[clap(flatten)]
+    pub opts: BuildArgs,
+
+    #[clap(env = "ETH_RPC_URL", long = "rpc-url", help = "the RPC endpoint of the chain")]
+    pub rpc_url: String,
+
+    #[clap(
+        long,
+        help = "the transaction which created the contract, looked up on Etherscan if not set"
+    )]
+    pub creation_tx: Option<H256>,
+
+    #[clap(long, env = "ETHERSCAN_API_KEY")]
+    pub etherscan_api_key: Option<String>,
+}
+
+impl Cmd for VerifyBytecodeArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let project = self.opts.project()?;
+        let compiled = self.opts.compile(&project)?;
+        let (abi, bin, runtime_bin) =
+            super::read_artifact(&project, compiled, self.contract.clone())?;
+        let (creation_code, runtime_code) = match (bin.into_bytes(), runtime_bin.into_bytes()) {
+            (Some(bin), Some(runtime_bin)) => (bin, runtime_bin),
+            _ => eyre::bail!("{} has unlinked library references", self.contract.name),
+        };
+
+        let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+        let provider = Provider::<Http>::try_from(self.rpc_url.as_str())?;
+
+        let onchain = rt.block_on(provider.get_code(self.address, None))?;
+        if onchain.as_ref().is_empty() {
+            eyre::bail!("no code deployed at {:?}", self.address)
+        }
+        let runtime = compare_runtime(runtime_code.as_ref(), onchain.as_ref());
+        println!("runtime code:     {}", paint(runtime));
+
+        let creation_input = rt.block_on(self.creation_input(&provider))?;
+        let creation = match creation_input {
+            Some(input) => {
+                let (creation, args) = compare_creation(creation_code.as_ref(), input.as_ref());
+                println!("creation code:    {}", paint(creation));
+                if creation.is_match() {
+                    print_constructor_args(&abi, args)?;
+                }
+                Some(creation)
+            }
+            None => {
+                println!(
+                    "creation code:    {}",
+                    Colour::Yellow.paint("skipped (deployed by a contract)")
+                );
+                None
+            }
+        };
+
+        if !runtime.is_match() || creation.map(|c| !c.is_match()).unwrap_or_default() {
+            eyre::bail!("the deployed bytecode does not match {}", self.contract.name)
+        }
+        Ok(())
+    }
+}
+
+impl VerifyBytecodeArgs {
+    /// Returns the input of the contract creation transaction, `None` if the contract was created
+    /// by another contract
+    async fn creation_input(
+        &self,
+        provider: &Provider<Http>,
+    ) -> eyre::Result<Option<ethers::types::Bytes>> {
+        let tx_hash = match self.creation_tx {
+            Some(tx_hash) => tx_hash,
+            None => {
+                let api_key = self.etherscan_api_key.as_deref().ok_or_else(|| {
+                    eyre::eyre!(
+                        "set ETHERSCAN_API_KEY or pass --creation-tx to compare the creation code"
+                    )
+                })?;
+                let chain_id = provider.get_chainid().await?.as_u64();
+                foundry_utils::etherscan::contract_creation_tx(chain_id, api_key, self.address)
+                    .await?
+            }
+        };
+        let tx = provider
+            .get_transaction(tx_hash)
+            .await?
+            .ok_or_else(|| eyre::eyre!("creation transaction {:?} not found", tx_hash))?;
+        Ok(if tx.to.is_none() { Some(tx.input) } else { None })
+    }
+}
+
+fn paint(res: BytecodeMatch) -> ansi_term::ANSIGenericString<'static, str> {
+    let msg = res.to_string();
+    match res {
+        BytecodeMatch::Exact => Colour::Green.paint(msg),
+        BytecodeMatch::Partial(_) => Colour::Yellow.paint(msg),
+        BytecodeMatch::Mismatch => Colour::Red.paint(msg),
+    }
+}
+
+fn print_constructor_args(abi: &Abi, args: &[u8]) -> eyre::Result<()> {
+    let types = abi
+        .constructor
+        .as_ref()
+        .map(|c| c.inputs.iter().map(|input| input.kind.clone()).collect::<Vec<ParamType>>())
+        .unwrap_or_default();
+    if types.is_empty() {
+        if !args.is_empty() {
+            println!("constructor args: 0x{} (no constructor)", hex::encode(args));
+        }
+        return Ok(())
+    }
+    let tokens = ethers::abi::decode(&types, args)?;
+    let tokens = foundry_utils::format_tokens(&tokens).collect::<Vec<_>>();
+    println!("constructor args: {}", tokens.join(", "));
+    Ok(())
+}
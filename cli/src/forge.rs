@@ -27,11 +27,20 @@ fn main() -> eyre::Result<()> {
         Subcommands::Run(cmd) => {
             cmd.run()?;
         }
+        Subcommands::Repl(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::UpgradeCheck(cmd) => {
+            cmd.run()?;
+        }
         Subcommands::VerifyContract { contract, address, constructor_args } => {
             let FullContractInfo { path, name } = contract;
             let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
             rt.block_on(cmd::verify::run(path, name, address, constructor_args))?;
         }
+        Subcommands::VerifyBytecode(cmd) => {
+            cmd.run()?;
+        }
         Subcommands::Create(cmd) => {
             cmd.run()?;
         }
@@ -89,6 +98,13 @@ fn main() -> eyre::Result<()> {
                 // write the tests
                 let contract_path = test.join("Contract.t.sol");
                 std::fs::write(contract_path, include_str!("../../assets/ContractTemplate.t.sol"))?;
+                // write the console library, whose logs are printed under each test
+                let utils = test.join("utils");
+                std::fs::create_dir_all(&utils)?;
+                std::fs::write(
+                    utils.join("console.sol"),
+                    include_str!("../../assets/ConsoleTemplate.sol"),
+                )?;
 
                 // sets up git
                 let is_git = Command::new("git")
@@ -1,7 +1,10 @@
 use std::{path::PathBuf, str::FromStr};
 
+use cast::tx::{BlobSidecar, TxOptions, TxType};
 use clap::{Parser, Subcommand};
-use ethers::types::{Address, BlockId, BlockNumber, NameOrAddress, H256};
+use ethers::types::{
+    transaction::eip2930::AccessList, Address, BlockId, BlockNumber, NameOrAddress, H256, U256,
+};
 
 use super::{ClapChain, EthereumOpts, Wallet};
 
@@ -140,6 +143,8 @@ pub enum Subcommands {
         cast_async: bool,
         #[clap(flatten)]
         eth: EthereumOpts,
+        #[clap(flatten)]
+        tx: TxArgs,
     },
     #[clap(name = "estimate")]
     #[clap(about = "Estimate the gas cost of a transaction from <from> to <to> with <data>")]
@@ -150,6 +155,11 @@ pub enum Subcommands {
         sig: String,
         #[clap(help = "the list of arguments you want to call the function with")]
         args: Vec<String>,
+        #[clap(
+            long,
+            help = "print the estimated fee, including the L1 data fee when connected to a rollup"
+        )]
+        cost: bool,
         #[clap(flatten)]
         eth: EthereumOpts,
     },
@@ -382,6 +392,67 @@ pub enum WalletSubcommands {
     },
 }
 
+#[derive(Debug, Clone, Parser)]
+pub struct TxArgs {
+    #[clap(
+        long = "tx-type",
+        help = "the transaction type: legacy, access-list, eip1559 or blob, defaults to eip1559 (legacy on rollups without EIP-1559). Blob transactions are signed by the node, see --blob-sidecar"
+    )]
+    pub tx_type: Option<TxType>,
+
+    #[clap(long, help = "send a legacy transaction", conflicts_with = "tx-type")]
+    pub legacy: bool,
+
+    #[clap(
+        long = "no-legacy",
+        help = "send an EIP-1559 transaction, even on rollups which default to legacy transactions",
+        conflicts_with_all = &["tx-type", "legacy"]
+    )]
+    pub no_legacy: bool,
+
+    #[clap(
+        long = "access-list",
+        help = "the access list to include as json, e.g. '[{\"address\":\"0x..\",\"storageKeys\":[\"0x..\"]}]'. Created by the node for access list transactions if not set",
+        parse(try_from_str = parse_access_list)
+    )]
+    pub access_list: Option<AccessList>,
+
+    #[clap(
+        long = "blob-sidecar",
+        help = "json file with the blobs, commitments and proofs of a blob transaction. Blob transactions can not be signed locally, they are sent with `eth_sendTransaction` and signed by the node for the unlocked --from account, so private keys, mnemonics and hardware wallets are not supported"
+    )]
+    pub blob_sidecar: Option<PathBuf>,
+
+    #[clap(
+        long = "blob-gas-price",
+        help = "the max fee per blob gas of a blob transaction, defaults to the current blob base fee",
+        requires = "blob-sidecar"
+    )]
+    pub blob_gas_price: Option<U256>,
+}
+
+impl TxArgs {
+    /// Returns the configured transaction options, loading the blob sidecar if any
+    pub fn options(&self) -> eyre::Result<TxOptions> {
+        Ok(TxOptions {
+            tx_type: if self.legacy {
+                Some(TxType::Legacy)
+            } else if self.no_legacy {
+                Some(TxType::Eip1559)
+            } else {
+                self.tx_type
+            },
+            access_list: self.access_list.clone(),
+            max_fee_per_blob_gas: self.blob_gas_price,
+            blobs: self.blob_sidecar.as_ref().map(BlobSidecar::load).transpose()?,
+        })
+    }
+}
+
+fn parse_access_list(s: &str) -> eyre::Result<AccessList> {
+    Ok(serde_json::from_str(s)?)
+}
+
 fn parse_name_or_address(s: &str) -> eyre::Result<NameOrAddress> {
     Ok(if s.starts_with("0x") {
         NameOrAddress::Address(s.parse::<Address>()?)
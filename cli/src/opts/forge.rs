@@ -4,7 +4,8 @@ use ethers::{solc::EvmVersion, types::Address};
 use std::{path::PathBuf, str::FromStr};
 
 use crate::cmd::{
-    build::BuildArgs, create::CreateArgs, remappings::RemappingArgs, run::RunArgs, snapshot, test,
+    build::BuildArgs, create::CreateArgs, remappings::RemappingArgs, repl::ReplArgs, run::RunArgs,
+    snapshot, test, upgrade_check::UpgradeCheckArgs, verify_bytecode::VerifyBytecodeArgs,
 };
 
 #[derive(Debug, Parser)]
@@ -30,6 +31,14 @@ pub enum Subcommands {
     #[clap(alias = "r")]
     Run(RunArgs),
 
+    #[clap(about = "start an interactive solidity shell")]
+    Repl(ReplArgs),
+
+    #[clap(
+        about = "compares the storage layouts of two implementations and reports changes which would corrupt the storage of a proxy"
+    )]
+    UpgradeCheck(UpgradeCheckArgs),
+
     #[clap(alias = "u", about = "fetches all upstream lib changes")]
     Update {
         #[clap(
@@ -66,6 +75,11 @@ pub enum Subcommands {
         constructor_args: Vec<String>,
     },
 
+    #[clap(
+        about = "compares the bytecode of a local contract with the bytecode deployed at an address"
+    )]
+    VerifyBytecode(VerifyBytecodeArgs),
+
     #[clap(alias = "c", about = "deploy a compiled contract")]
     Create(CreateArgs),
 
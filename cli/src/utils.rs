@@ -1,7 +1,22 @@
-use ethers::solc::{artifacts::Contract, EvmVersion};
+use ansi_term::Colour;
+use ethers::{
+    abi::Abi,
+    providers::{Middleware, Provider},
+    solc::{artifacts::Contract, EvmVersion, Project},
+    types::{Address, U256},
+};
+use evm_adapters::{call_tracing::CallTraceArena, evm_opts::EvmOpts, state_diff::StateDiff};
+use forge::StorageLayout;
+use foundry_utils::etherscan::{etherscan_chain, EtherscanIdentifier};
 
 use eyre::{ContextCompat, WrapErr};
-use std::{path::PathBuf, process::Command};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::SystemTime,
+};
 
 #[cfg(feature = "evmodin-evm")]
 use evmodin::Revision;
@@ -65,6 +80,103 @@ pub fn find_git_root_path() -> eyre::Result<PathBuf> {
     Ok(PathBuf::from(path))
 }
 
+/// The file in the project root declaring pipeline hooks, one `<stage>: <command>` per line, e.g.
+/// `pre_build: ./scripts/generate.sh`. Lines starting with `#` are ignored
+pub const HOOKS_FILE: &str = "hooks.txt";
+
+/// The stages hooks can be declared for
+const HOOK_STAGES: &[&str] = &["pre_build", "post_build", "pre_test"];
+
+/// Runs the commands of a pipeline hook through the shell in the project root, aborting on the
+/// first command which fails. The commands declared in the project's [`HOOKS_FILE`] run before
+/// the provided ones.
+///
+/// Everything the hooks print goes to stderr as it is printed, so stdout stays parseable with
+/// `--json`
+pub fn run_hooks(stage: &str, commands: &[String], root: impl AsRef<Path>) -> eyre::Result<()> {
+    let root = root.as_ref();
+    for cmd in configured_hooks(stage, root)?.iter().chain(commands) {
+        eprintln!("running {} hook `{}`...", stage, cmd);
+        let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let mut child = Command::new(shell)
+            .args(&[flag, cmd.as_str()])
+            .current_dir(root)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .wrap_err_with(|| format!("could not run {} hook `{}`", stage, cmd))?;
+        if let Some(mut stdout) = child.stdout.take() {
+            std::io::copy(&mut stdout, &mut std::io::stderr())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            eyre::bail!("{} hook `{}` failed with {}", stage, cmd, status)
+        }
+    }
+    Ok(())
+}
+
+/// Runs the `pre_build` hooks, see [`run_hooks`].
+///
+/// The compiler cache is removed if the hooks created, changed or removed any of the project's
+/// sources, so that generated sources are always compiled.
+pub fn run_pre_build_hooks(project: &Project, commands: &[String]) -> eyre::Result<()> {
+    let mut before = BTreeMap::new();
+    modification_times(&project.paths.sources, &mut before);
+    run_hooks("pre_build", commands, &project.paths.root)?;
+    let mut after = BTreeMap::new();
+    modification_times(&project.paths.sources, &mut after);
+    if project.cached && before != after && project.paths.cache.exists() {
+        std::fs::remove_file(&project.paths.cache)?;
+    }
+    Ok(())
+}
+
+/// Collects the modification times of all files below the directory
+fn modification_times(dir: &Path, times: &mut BTreeMap<PathBuf, Option<SystemTime>>) {
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            modification_times(&path, times);
+        } else {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            times.insert(path, modified);
+        }
+    }
+}
+
+/// Returns the commands declared for the stage in the project's [`HOOKS_FILE`], if any
+fn configured_hooks(stage: &str, root: &Path) -> eyre::Result<Vec<String>> {
+    let path = root.join(HOOKS_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(content) => parse_hooks(&content, stage)
+            .wrap_err_with(|| format!("invalid hooks file {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).wrap_err_with(|| format!("failed to read {}", path.display())),
+    }
+}
+
+fn parse_hooks(content: &str, stage: &str) -> eyre::Result<Vec<String>> {
+    let mut commands = Vec::new();
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let (hook_stage, cmd) = line
+            .split_once(':')
+            .map(|(hook_stage, cmd)| (hook_stage.trim(), cmd.trim()))
+            .filter(|(hook_stage, cmd)| HOOK_STAGES.contains(hook_stage) && !cmd.is_empty())
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "expected `<stage>: <command>` with a stage of {}, got `{}`",
+                    HOOK_STAGES.join(", "),
+                    line
+                )
+            })?;
+        if hook_stage == stage {
+            commands.push(cmd.to_string());
+        }
+    }
+    Ok(commands)
+}
+
 #[cfg(feature = "sputnik-evm")]
 pub fn sputnik_cfg(evm: &EvmVersion) -> Config {
     match evm {
@@ -98,3 +210,88 @@ pub fn read_secret(secret: bool, unsafe_secret: Option<String>) -> eyre::Result<
         unsafe_secret.unwrap()
     })
 }
+
+/// Resolves the names and ABIs of the external contracts called in the traces from their verified
+/// source on Etherscan, skipping the contracts which are already identified.
+///
+/// Only done for runs against a fork with `--etherscan-identify`. Responses are cached in
+/// `cache_dir`, per chain.
+pub fn etherscan_identify(
+    rt: &tokio::runtime::Runtime,
+    evm_opts: &EvmOpts,
+    cache_dir: &Path,
+    traces: &[CallTraceArena],
+    identified: &mut BTreeMap<Address, (String, Abi)>,
+) {
+    let fork_url = match (&evm_opts.fork_url, evm_opts.etherscan_identify) {
+        (Some(fork_url), true) => fork_url,
+        _ => return,
+    };
+    let api_key = match evm_opts.etherscan_api_key {
+        Some(ref api_key) => api_key,
+        None => {
+            tracing::warn!("--etherscan-identify requires an Etherscan API key");
+            return
+        }
+    };
+    let unknown = traces
+        .iter()
+        .flat_map(|trace| trace.external_addresses())
+        .filter(|addr| !identified.contains_key(addr))
+        .collect::<BTreeSet<_>>();
+    if unknown.is_empty() {
+        return
+    }
+
+    let res = rt.block_on(async {
+        let provider = Provider::try_from(fork_url.as_str())?;
+        let chain_id = provider.get_chainid().await?;
+        let chain = etherscan_chain(chain_id.as_u64())
+            .ok_or_else(|| eyre::eyre!("chain {} is not supported by Etherscan", chain_id))?;
+        let cache_dir = cache_dir.join(chain_id.to_string());
+        let identifier = EtherscanIdentifier::new(chain, api_key.as_str(), Some(cache_dir))?;
+        Ok::<_, eyre::Error>(identifier.identify_all(unknown).await)
+    });
+    match res {
+        Ok(contracts) => identified.extend(contracts),
+        Err(err) => tracing::warn!(?err, "could not resolve contracts from Etherscan"),
+    }
+}
+
+/// Prints the accounts changed in the state diff, naming the storage slots of known contracts
+/// with their storage layout
+pub fn print_state_diff(diff: &StateDiff, storage_layouts: &BTreeMap<String, StorageLayout>) {
+    println!("State diff:");
+    if diff.is_empty() {
+        println!("  no changes");
+        return
+    }
+    for (address, account) in &diff.accounts {
+        let mut header = format!("  {:?}", address);
+        if let Some(ref contract) = account.contract {
+            header.push_str(&format!(" ({})", contract));
+        }
+        if account.created {
+            header.push_str(&format!(" {}", Colour::Green.paint("[created]")));
+        }
+        if account.destroyed {
+            header.push_str(&format!(" {}", Colour::Red.paint("[destroyed]")));
+        }
+        println!("{}", header);
+
+        if let Some((before, after)) = account.balance {
+            println!("    balance: {} → {}", before, after);
+        }
+        if let Some((before, after)) = account.nonce {
+            println!("    nonce: {} → {}", before, after);
+        }
+        let layout = account.contract.as_ref().and_then(|contract| storage_layouts.get(contract));
+        for (slot, (before, after)) in &account.storage {
+            let label = layout
+                .and_then(|layout| layout.slot_label(U256::from_big_endian(slot.as_bytes())))
+                .map(|label| format!(" ({})", label))
+                .unwrap_or_default();
+            println!("    @ {:?}{}: {:?} → {:?}", slot, label, before, after);
+        }
+    }
+}
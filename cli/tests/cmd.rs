@@ -35,3 +35,57 @@ forgetest!(can_clean_hardhat, PathStyle::HardHat, |prj: TestProject, mut cmd: Te
     cmd.assert_empty_stdout();
     prj.assert_cleaned();
 });
+
+// checks that a failing `pre_build` hook aborts the build
+forgetest!(failing_pre_build_hook_aborts, |_: TestProject, mut cmd: TestCommand| {
+    cmd.args(["build", "--pre-build", "exit 1"]);
+    let output = cmd.cmd().output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pre_build hook `exit 1` failed"));
+});
+
+// checks that hooks print to stderr and don't break the json output of `test`
+forgetest!(hooks_keep_json_output_parseable, |prj: TestProject, mut cmd: TestCommand| {
+    prj.paths().create_all().unwrap();
+    std::fs::write(
+        prj.paths().sources.join("Hook.t.sol"),
+        "pragma solidity ^0.8.0; contract HookTest { function testHook() public {} }",
+    )
+    .unwrap();
+    cmd.args(["test", "--json", "--pre-build", "echo pre-build", "--pre-test", "echo pre-test"]);
+    let output = cmd.output();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stdout.contains("pre-build") && !stdout.contains("pre-test"));
+    assert!(stderr.contains("pre-build") && stderr.contains("pre-test"));
+    let results: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+    assert!(results.to_string().contains("testHook"));
+});
+
+// checks that hooks declared in `hooks.txt` run and their generated sources are compiled
+forgetest!(configured_hooks_regenerate_sources, |prj: TestProject, mut cmd: TestCommand| {
+    prj.paths().create_all().unwrap();
+    std::fs::write(
+        prj.paths().sources.join("Greeter.sol"),
+        "pragma solidity ^0.8.0; contract Greeter {}",
+    )
+    .unwrap();
+    cmd.arg("build");
+    assert!(String::from_utf8_lossy(&cmd.output().stdout).contains("success."));
+
+    let generated = prj.paths().sources.join("Generated.sol");
+    std::fs::write(
+        prj.root().join("hooks.txt"),
+        format!(
+            "# generates a contract\npre_build: echo 'pragma solidity ^0.8.0; contract Generated {{}}' > {}\n",
+            generated.display()
+        ),
+    )
+    .unwrap();
+    let mut cmd = prj.command();
+    cmd.arg("build");
+    let output = cmd.output();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("running pre_build hook"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("success."));
+    assert!(generated.exists());
+});
@@ -1,17 +1,25 @@
 use ethers::{
-    abi::{Abi, FunctionExt, RawLog},
-    types::{H160, U256},
+    abi::{Abi, Function, FunctionExt, RawLog},
+    types::{H160, H256, U256},
 };
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use ansi_term::Colour;
 
 use foundry_utils::format_token;
 
+use crate::signatures::SignatureCache;
+
 #[cfg(feature = "sputnik")]
 use crate::sputnik::cheatcodes::{cheatcode_handler::CHEATCODE_ADDRESS, HEVM_ABI};
 
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`
+pub const EIP1967_IMPLEMENTATION_SLOT: [u8; 32] = [
+    0x36, 0x08, 0x94, 0xa1, 0x3b, 0xa1, 0xa3, 0x21, 0x06, 0x67, 0xc8, 0x28, 0x49, 0x2d, 0xb9, 0x8d,
+    0xca, 0x3e, 0x20, 0x76, 0xcc, 0x37, 0x35, 0xa9, 0x20, 0xa3, 0xca, 0x50, 0x5d, 0x38, 0x2b, 0xbc,
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 /// An arena of `CallTraceNode`s
 pub struct CallTraceArena {
@@ -101,6 +109,19 @@ impl CallTraceArena {
         node.trace.update(trace);
     }
 
+    /// Returns the addresses called in the trace which were not created in it, i.e. the
+    /// contracts which existed before the traced execution
+    pub fn external_addresses(&self) -> BTreeSet<H160> {
+        let created =
+            self.arena.iter().filter(|node| node.trace.created).map(|node| node.trace.addr);
+        let created = created.collect::<BTreeSet<_>>();
+        self.arena
+            .iter()
+            .map(|node| node.trace.addr)
+            .filter(|addr| !created.contains(addr))
+            .collect()
+    }
+
     /// Updates `identified_contracts` for future use so that after an `evm.reset_state()`, we
     /// already know which contract corresponds to which address.
     ///
@@ -165,6 +186,9 @@ impl CallTraceArena {
     /// `contracts` are the known contracts of (name => (abi, runtime_code)). It is used to identify
     /// a deployed contract.
     ///
+    /// `signatures` is the index of known selectors and event topics used to decode calls and
+    /// logs, see [`SignatureCache`].
+    ///
     /// `identified_contracts` are the identified contract addresses built up from comparing
     /// deployed contracts against `contracts`
     ///
@@ -178,6 +202,7 @@ impl CallTraceArena {
         &self,
         idx: usize,
         contracts: &BTreeMap<String, (Abi, Vec<u8>)>,
+        signatures: &SignatureCache,
         identified_contracts: &mut BTreeMap<H160, (String, Abi)>,
         evm: &'a E,
         left: &str,
@@ -200,6 +225,26 @@ impl CallTraceArena {
         #[cfg(not(feature = "sputnik"))]
         let color = if trace.success { Colour::Green } else { Colour::Red };
 
+        // calls forwarded by a proxy are decoded against the implementation
+        if !trace.created {
+            if let Some((name, abi)) = self.resolve_proxy(idx, contracts, identified_contracts, evm)
+            {
+                let output =
+                    trace.print_func_call(Some(&abi), Some(&name), signatures, color, left);
+                self.print_children_and_logs(
+                    idx,
+                    Some(&abi),
+                    contracts,
+                    signatures,
+                    identified_contracts,
+                    evm,
+                    left,
+                );
+                output.print(color, left);
+                return
+            }
+        }
+
         // we have to clone the name and abi because identified_contracts is later borrowed
         // immutably
         let res = if let Some((name, abi)) = identified_contracts.get(&trace.addr) {
@@ -222,6 +267,7 @@ impl CallTraceArena {
                         idx,
                         Some(abi),
                         contracts,
+                        signatures,
                         identified_contracts,
                         evm,
                         left,
@@ -234,12 +280,20 @@ impl CallTraceArena {
                     );
                 } else {
                     // re-enter this function at the current node
-                    self.pretty_print(idx, contracts, identified_contracts, evm, left);
+                    self.pretty_print(idx, contracts, signatures, identified_contracts, evm, left);
                 }
             } else if trace.created {
                 // we couldn't identify, print the children and logs without the abi
                 println!("{}{} <Unknown>@{}", left, Colour::Yellow.paint("→ new"), trace.addr);
-                self.print_children_and_logs(idx, None, contracts, identified_contracts, evm, left);
+                self.print_children_and_logs(
+                    idx,
+                    None,
+                    contracts,
+                    signatures,
+                    identified_contracts,
+                    evm,
+                    left,
+                );
                 println!(
                     "{}  └─ {} {} bytes of code",
                     left.replace("├─", "│").replace("└─", "  "),
@@ -247,8 +301,16 @@ impl CallTraceArena {
                     trace.output.len()
                 );
             } else {
-                let output = trace.print_func_call(None, None, color, left);
-                self.print_children_and_logs(idx, None, contracts, identified_contracts, evm, left);
+                let output = trace.print_func_call(None, None, signatures, color, left);
+                self.print_children_and_logs(
+                    idx,
+                    None,
+                    contracts,
+                    signatures,
+                    identified_contracts,
+                    evm,
+                    left,
+                );
                 output.print(color, left);
             }
         } else if let Some((name, abi)) = res {
@@ -258,6 +320,7 @@ impl CallTraceArena {
                     idx,
                     Some(&abi),
                     contracts,
+                    signatures,
                     identified_contracts,
                     evm,
                     left,
@@ -269,11 +332,13 @@ impl CallTraceArena {
                     trace.output.len()
                 );
             } else {
-                let output = trace.print_func_call(Some(&abi), Some(&name), color, left);
+                let output =
+                    trace.print_func_call(Some(&abi), Some(&name), signatures, color, left);
                 self.print_children_and_logs(
                     idx,
                     Some(&abi),
                     contracts,
+                    signatures,
                     identified_contracts,
                     evm,
                     left,
@@ -283,12 +348,58 @@ impl CallTraceArena {
         }
     }
 
+    /// Resolves the implementation of the proxy called at `idx`, returning the `Proxy→Impl`
+    /// label and the implementation's abi.
+    ///
+    /// Calls which can be decoded against the contract's own abi are not considered forwarded.
+    /// The implementation is the target of a delegatecall forwarding the calldata as is, or
+    /// otherwise the address stored in the EIP-1967 implementation slot.
+    pub fn resolve_proxy<'a, S: Clone, E: crate::Evm<S>>(
+        &self,
+        idx: usize,
+        contracts: &BTreeMap<String, (Abi, Vec<u8>)>,
+        identified_contracts: &mut BTreeMap<H160, (String, Abi)>,
+        evm: &'a E,
+    ) -> Option<(String, Abi)> {
+        let node = &self.arena[idx];
+        let trace = &node.trace;
+
+        identify_deployed(trace.addr, contracts, identified_contracts, evm);
+        let proxy_name = match identified_contracts.get(&trace.addr) {
+            Some((_, abi))
+                if trace.data.len() >= 4 &&
+                    abi.functions().any(|func| func.selector() == trace.data[0..4]) =>
+            {
+                return None
+            }
+            Some((name, _)) => name.clone(),
+            None => format!("{}", trace.addr),
+        };
+
+        let implementation = node
+            .children
+            .iter()
+            .map(|child| &self.arena[*child].trace)
+            .find(|child| child.delegate && child.data == trace.data)
+            .map(|child| child.addr)
+            .or_else(|| {
+                let slot = evm.storage(trace.addr, H256::from(EIP1967_IMPLEMENTATION_SLOT));
+                (!slot.is_zero()).then(|| H160::from(slot))
+            })
+            .filter(|implementation| *implementation != trace.addr)?;
+
+        identify_deployed(implementation, contracts, identified_contracts, evm);
+        let (name, abi) = identified_contracts.get(&implementation)?;
+        Some((format!("{}→{}", proxy_name, name), abi.clone()))
+    }
+
     /// Prints child calls and logs in order
     pub fn print_children_and_logs<'a, S: Clone, E: crate::Evm<S>>(
         &self,
         node_idx: usize,
         abi: Option<&Abi>,
         contracts: &BTreeMap<String, (Abi, Vec<u8>)>,
+        signatures: &SignatureCache,
         identified_contracts: &mut BTreeMap<H160, (String, Abi)>,
         evm: &'a E,
         left: &str,
@@ -298,12 +409,13 @@ impl CallTraceArena {
         // logs and calls in the correct order
         self.arena[node_idx].ordering.iter().for_each(|ordering| match ordering {
             LogCallOrder::Log(index) => {
-                self.arena[node_idx].print_log(*index, abi, left);
+                self.arena[node_idx].print_log(*index, abi, signatures, left);
             }
             LogCallOrder::Call(index) => {
                 self.pretty_print(
                     self.arena[node_idx].children[*index],
                     contracts,
+                    signatures,
                     identified_contracts,
                     evm,
                     &(left.replace("├─", "│").replace("└─", "  ") + "  ├─ "),
@@ -332,28 +444,36 @@ pub struct CallTraceNode {
 }
 
 impl CallTraceNode {
-    /// Prints a log at a particular index, optionally decoding if abi is provided
-    pub fn print_log(&self, index: usize, abi: Option<&Abi>, left: &str) {
+    /// Prints a log at a particular index, decoding it if its topic is part of the provided abi
+    /// or indexed in `signatures`
+    pub fn print_log(
+        &self,
+        index: usize,
+        abi: Option<&Abi>,
+        signatures: &SignatureCache,
+        left: &str,
+    ) {
         let log = &self.logs[index];
         let right = "  ├─ ";
-        if let Some(abi) = abi {
-            for (event_name, overloaded_events) in abi.events.iter() {
-                for event in overloaded_events.iter() {
-                    if event.signature() == log.topics[0] {
-                        let params = event.parse_log(log.clone()).expect("Bad event").params;
-                        let strings = params
-                            .into_iter()
-                            .map(|param| format!("{}: {}", param.name, format_token(&param.value)))
-                            .collect::<Vec<String>>()
-                            .join(", ");
-                        println!(
-                            "{}emit {}({})",
-                            left.replace("├─", "│") + right,
-                            Colour::Cyan.paint(event_name),
-                            strings
-                        );
-                        return
-                    }
+        if let Some(topic) = log.topics.first() {
+            let event = abi
+                .and_then(|abi| signatures.abi_event(abi, topic))
+                .or_else(|| signatures.event(topic));
+            if let Some(event) = event {
+                if let Ok(parsed) = event.parse_log(log.clone()) {
+                    let strings = parsed
+                        .params
+                        .into_iter()
+                        .map(|param| format!("{}: {}", param.name, format_token(&param.value)))
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    println!(
+                        "{}emit {}({})",
+                        left.replace("├─", "│") + right,
+                        Colour::Cyan.paint(&event.name),
+                        strings
+                    );
+                    return
                 }
             }
         }
@@ -394,6 +514,28 @@ pub enum LogCallOrder {
     Call(usize),
 }
 
+/// Identifies the contract deployed at `address` by comparing its code against `contracts`,
+/// unless it was already identified
+fn identify_deployed<S: Clone, E: crate::Evm<S>>(
+    address: H160,
+    contracts: &BTreeMap<String, (Abi, Vec<u8>)>,
+    identified_contracts: &mut BTreeMap<H160, (String, Abi)>,
+    evm: &E,
+) {
+    if identified_contracts.contains_key(&address) {
+        return
+    }
+    let code = evm.code(address);
+    if code.is_empty() {
+        return
+    }
+    if let Some((name, (abi, _code))) =
+        contracts.iter().find(|(_key, (_abi, known_code))| diff_score(known_code, &code) < 0.10)
+    {
+        identified_contracts.insert(address, (name.to_string(), abi.clone()));
+    }
+}
+
 /// Call trace of a tx
 #[derive(Clone, Default, Debug, Deserialize, Serialize)]
 pub struct CallTrace {
@@ -406,6 +548,9 @@ pub struct CallTrace {
     pub addr: H160,
     /// Creation
     pub created: bool,
+    /// Whether the callee's code was executed in the context of the caller, i.e. a delegatecall
+    #[serde(default)]
+    pub delegate: bool,
     /// Ether value transfer
     pub value: U256,
     /// Call data, including function selector (if applicable)
@@ -428,70 +573,25 @@ impl CallTrace {
     }
 
     /// Prints function call, returning the decoded or raw output
+    ///
+    /// Functions of identified contracts are looked up in `signatures` first, only falling back
+    /// to searching the contract's abi if the selector is not indexed for it.
     pub fn print_func_call(
         &self,
         abi: Option<&Abi>,
         name: Option<&String>,
+        signatures: &SignatureCache,
         color: Colour,
         left: &str,
     ) -> Output {
         if let (Some(abi), Some(name)) = (abi, name) {
             // Is data longer than 4, meaning we can attempt to decode it
             if self.data.len() >= 4 {
-                for (func_name, overloaded_funcs) in abi.functions.iter() {
-                    for func in overloaded_funcs.iter() {
-                        if func.selector() == self.data[0..4] {
-                            let mut strings = "".to_string();
-                            if !self.data[4..].is_empty() {
-                                let params = func
-                                    .decode_input(&self.data[4..])
-                                    .expect("Bad func data decode");
-                                strings =
-                                    params.iter().map(format_token).collect::<Vec<_>>().join(", ");
-
-                                #[cfg(feature = "sputnik")]
-                                if self.addr == *CHEATCODE_ADDRESS && func.name == "expectRevert" {
-                                    // try to decode better than just `bytes` for `expectRevert`
-                                    if let Ok(decoded) = foundry_utils::decode_revert(&self.data) {
-                                        strings = decoded;
-                                    }
-                                }
-                            }
-
-                            println!(
-                                "{}[{}] {}::{}{}({})",
-                                left,
-                                self.cost,
-                                color.paint(name),
-                                color.paint(func_name),
-                                if self.value > 0.into() {
-                                    format!("{{value: {}}}", self.value)
-                                } else {
-                                    "".to_string()
-                                },
-                                strings,
-                            );
-
-                            if !self.output.is_empty() && self.success {
-                                return Output::Token(
-                                    func.decode_output(&self.output[..])
-                                        .expect("Bad func output decode"),
-                                )
-                            } else if !self.output.is_empty() && !self.success {
-                                if let Ok(decoded_error) =
-                                    foundry_utils::decode_revert(&self.output[..])
-                                {
-                                    return Output::Token(vec![ethers::abi::Token::String(
-                                        decoded_error,
-                                    )])
-                                } else {
-                                    return Output::Raw(self.output.clone())
-                                }
-                            } else {
-                                return Output::Raw(vec![])
-                            }
-                        }
-                    }
+                let func = signatures
+                    .abi_function(abi, &self.data)
+                    .or_else(|| signatures.function(&self.data));
+                if let Some(func) = func {
+                    return self.print_decoded_func_call(func, name, color, left)
                 }
             } else {
                 // fallback function
@@ -514,6 +614,9 @@ impl CallTrace {
                 }
                 return Output::Raw(self.output[..].to_vec())
             }
+        } else if let Some(func) = signatures.function(&self.data) {
+            // the contract is unknown, but we have seen the selector before
+            return self.print_decoded_func_call(func, &format!("{}", self.addr), color, left)
         }
 
         // We couldn't decode the function call, so print it as an abstract call
@@ -546,11 +649,66 @@ impl CallTrace {
         }
         Output::Raw(self.output[..].to_vec())
     }
+
+    /// Prints a function call decoded with `func`, returning the decoded output
+    fn print_decoded_func_call(
+        &self,
+        func: &Function,
+        name: &str,
+        color: Colour,
+        left: &str,
+    ) -> Output {
+        let mut strings = "".to_string();
+        if !self.data[4..].is_empty() {
+            strings = match func.decode_input(&self.data[4..]) {
+                Ok(params) => params.iter().map(format_token).collect::<Vec<_>>().join(", "),
+                // the selector matched, but the input did not
+                Err(_) => hex::encode(&self.data[4..]),
+            };
+
+            #[cfg(feature = "sputnik")]
+            if self.addr == *CHEATCODE_ADDRESS && func.name == "expectRevert" {
+                // try to decode better than just `bytes` for `expectRevert`
+                if let Ok(decoded) = foundry_utils::decode_revert(&self.data) {
+                    strings = decoded;
+                }
+            }
+        }
+
+        println!(
+            "{}[{}] {}::{}{}({})",
+            left,
+            self.cost,
+            color.paint(name),
+            color.paint(&func.name),
+            if self.value > 0.into() {
+                format!("{{value: {}}}", self.value)
+            } else {
+                "".to_string()
+            },
+            strings,
+        );
+
+        if !self.output.is_empty() && self.success {
+            match func.decode_output(&self.output[..]) {
+                Ok(tokens) => Output::Token(tokens),
+                Err(_) => Output::Raw(self.output.clone()),
+            }
+        } else if !self.output.is_empty() && !self.success {
+            if let Ok(decoded_error) = foundry_utils::decode_revert(&self.output[..]) {
+                Output::Token(vec![ethers::abi::Token::String(decoded_error)])
+            } else {
+                Output::Raw(self.output.clone())
+            }
+        } else {
+            Output::Raw(vec![])
+        }
+    }
 }
 
 // very simple fuzzy matching to account for immutables. Will fail for small contracts that are
 // basically all immutable vars
-fn diff_score(bytecode1: &[u8], bytecode2: &[u8]) -> f64 {
+pub(crate) fn diff_score(bytecode1: &[u8], bytecode2: &[u8]) -> f64 {
     let cutoff_len = usize::min(bytecode1.len(), bytecode2.len());
     let b1 = &bytecode1[..cutoff_len];
     let b2 = &bytecode2[..cutoff_len];
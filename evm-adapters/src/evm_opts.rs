@@ -1,6 +1,7 @@
-use clap::Parser;
+use crate::trace_format::TraceFormat;
+use clap::{Parser, ValueHint};
 use ethers::types::{Address, U256};
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr, time::Duration};
 
 #[cfg(feature = "evmodin")]
 use evmodin::util::mocked_host::MockedHost;
@@ -13,6 +14,9 @@ pub enum EvmType {
     Sputnik,
     #[cfg(feature = "evmodin")]
     EvmOdin,
+    /// An execution environment running in an external process, spawned with the given command,
+    /// see [`crate::external`]
+    External(String),
 }
 
 #[cfg(any(feature = "sputnik", feature = "evmodin"))]
@@ -33,6 +37,12 @@ impl FromStr for EvmType {
     type Err = eyre::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(cmd) = s.strip_prefix("external:") {
+            if cmd.trim().is_empty() {
+                eyre::bail!("missing command for external EVM")
+            }
+            return Ok(EvmType::External(cmd.trim().to_string()))
+        }
         // silence this warning which indicates that if no evm features are
         // enabled, the Ok(...) will never be reached.
         #[allow(unreachable_code)]
@@ -55,7 +65,7 @@ pub struct EvmOpts {
     #[clap(
         long,
         short,
-        help = "the EVM type you want to use (e.g. sputnik, evmodin)",
+        help = "the EVM type you want to use (e.g. sputnik, evmodin, or `external:<command>` to run a plugin process)",
         default_value = "sputnik"
     )]
     pub evm_type: EvmType,
@@ -68,6 +78,19 @@ pub struct EvmOpts {
     #[clap(env = "DAPP_FORK_BLOCK")]
     pub fork_block_number: Option<u64>,
 
+    #[clap(
+        help = "resolve the names and ABIs of external contracts in the traces of forked runs from their verified source on Etherscan. Requires an Etherscan API key",
+        long
+    )]
+    pub etherscan_identify: bool,
+
+    #[clap(
+        help = "the Etherscan API key used by --etherscan-identify",
+        long,
+        env = "ETHERSCAN_API_KEY"
+    )]
+    pub etherscan_api_key: Option<String>,
+
     #[clap(
         help = "the initial balance of each deployed test contract",
         long,
@@ -86,6 +109,18 @@ pub struct EvmOpts {
     #[clap(help = "enables the FFI cheatcode", long)]
     pub ffi: bool,
 
+    #[clap(
+        help = "allows the file cheatcodes (`readFile`, `writeJson`) to read and write inside the path, relative to the project root",
+        long = "allow-path",
+        value_hint = ValueHint::DirPath
+    )]
+    pub allowed_paths: Vec<PathBuf>,
+
+    /// The directory the file cheatcodes resolve relative paths against, set to the project root
+    /// when the tests are built. The working directory if empty
+    #[clap(skip)]
+    pub root: PathBuf,
+
     #[clap(
         help = r#"Verbosity mode of EVM output as number of occurences of the `v` flag (-v, -vv, -vvv, etc.)
     3: print test trace for failing tests
@@ -100,6 +135,47 @@ pub struct EvmOpts {
 
     #[clap(help = "enable debugger", long)]
     pub debug: bool,
+
+    #[clap(
+        help = "print the traces as json in the given format instead, one of parity, geth or json",
+        long
+    )]
+    pub trace_format: Option<TraceFormat>,
+
+    #[clap(
+        help = "print the accounts touched by each test or script, with their storage, balance and nonce changes",
+        long
+    )]
+    pub state_diff: bool,
+
+    #[clap(
+        help = "run each fuzz test for this long, e.g. `30s`, `10m` or `1h`, instead of for a fixed number of runs",
+        long,
+        parse(try_from_str = parse_duration)
+    )]
+    pub fuzz_max_time: Option<Duration>,
+
+    #[clap(
+        help = "stop fuzzing once the tests ran for this long in total, e.g. `30s`, `10m` or `1h`. Fuzz tests starting later run no cases",
+        long,
+        parse(try_from_str = parse_duration)
+    )]
+    pub fuzz_max_total_time: Option<Duration>,
+}
+
+/// Parses a duration in seconds, minutes or hours, e.g. `90s`, `10m` or `1h`. Plain numbers are
+/// seconds
+fn parse_duration(s: &str) -> eyre::Result<Duration> {
+    let (num, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let num = u64::from_str(num).map_err(|_| eyre::eyre!("invalid duration `{}`", s))?;
+    let secs = match unit {
+        "" | "s" => Some(num),
+        "m" => num.checked_mul(60),
+        "h" => num.checked_mul(60 * 60),
+        _ => eyre::bail!("invalid duration unit `{}`, expected s, m or h", unit),
+    };
+    let secs = secs.ok_or_else(|| eyre::eyre!("duration `{}` is too long", s))?;
+    Ok(Duration::from_secs(secs))
 }
 
 #[cfg(feature = "sputnik")]
@@ -257,3 +333,19 @@ impl Env {
         host
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert!(parse_duration("1d").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX)).is_err());
+    }
+}
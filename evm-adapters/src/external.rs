@@ -0,0 +1,433 @@
+//! Adapter for execution environments which run in an external process, e.g. zk-rollups whose
+//! VM does not execute EVM bytecode.
+//!
+//! The process is spawned once per [`ExternalEvm`] and receives one json request per line on
+//! its stdin, answering each with one json response per line on its stdout:
+//!
+//! ```text
+//! -> {"id":1,"method":"deploy","params":{"from":"0x..","code":"0x..","value":"0x0"}}
+//! <- {"id":1,"result":{"success":true,"address":"0x..","output":"0x","gas":21000,"logs":[]}}
+//! ```
+//!
+//! The supported methods are
+//! - `compile`: `{root, contracts: {name: {abi, bytecode}}}` => `{name: bytecode}`, optional, used
+//!   to replace the solc output with the environment's own bytecode
+//! - `deploy`: `{from, code, value}` => `{success, address, output, gas, logs}`
+//! - `call`: `{from, to, data, value, static}` => `{success, output, gas, logs}`
+//! - `set_code`: `{address, code}` => `null`
+//! - `set_balance`: `{address, amount}` => `null`
+//! - `code`: `{address}` => `bytes`
+//! - `storage`: `{address, slot}` => `bytes32`
+//! - `snapshot`: `{}` => `id`, snapshots the current state
+//! - `revert`: `{id}` => `null`, reverts to the given snapshot, which stays valid afterwards
+//! - `reset`: `{}` => `null`, reverts all changes made since the process was started
+//!
+//! Failures are reported as `{"id":1,"error":"message"}`. Every response must carry the id of the
+//! request it answers. Environments which do not implement an optional method answer it with the
+//! error `method not supported`.
+
+use crate::{Evm, EvmError};
+use ethers::{
+    abi::Abi,
+    types::{Address, Bytes, H256, U256},
+};
+use eyre::{Result, WrapErr};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+#[cfg(feature = "sputnik")]
+use crate::sputnik::cheatcodes::debugger::DebugArena;
+
+/// The error an environment answers optional methods it does not implement with
+pub const METHOD_NOT_SUPPORTED: &str = "method not supported";
+
+/// The state of an external environment, as seen from this process
+#[derive(Clone, Debug, Default)]
+pub struct ExternalState {
+    /// The snapshot to revert to on [`Evm::reset`], if any
+    pub snapshot: Option<u64>,
+}
+
+/// The outcome of an execution in an external environment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExternalExitReason {
+    Success,
+    Revert,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    /// The id of the request this responds to
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Execution {
+    success: bool,
+    #[serde(default)]
+    address: Address,
+    #[serde(default)]
+    output: Bytes,
+    #[serde(default)]
+    gas: u64,
+    #[serde(default)]
+    logs: Vec<String>,
+}
+
+impl Execution {
+    fn reason(&self) -> ExternalExitReason {
+        if self.success {
+            ExternalExitReason::Success
+        } else {
+            ExternalExitReason::Revert
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Connection {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// An [`Evm`] implementation which forwards all execution to an external process, see the
+/// [module documentation](self) for the protocol
+#[derive(Debug)]
+pub struct ExternalEvm {
+    command: String,
+    connection: RefCell<Connection>,
+    logs: Vec<String>,
+    state: ExternalState,
+}
+
+impl ExternalEvm {
+    /// Spawns the external environment. The command is run through the shell, so it may contain
+    /// arguments.
+    pub fn spawn(command: &str) -> Result<Self> {
+        let mut child = shell(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .wrap_err_with(|| format!("failed to spawn external evm `{}`", command))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let connection = Connection { child, stdin, stdout, next_id: 0 };
+        Ok(Self {
+            command: command.to_string(),
+            connection: RefCell::new(connection),
+            logs: Vec::new(),
+            state: ExternalState::default(),
+        })
+    }
+
+    /// Sends a request to the external process and waits for its response
+    fn request<R: DeserializeOwned>(&self, method: &str, params: Value) -> Result<R> {
+        self.try_request(method, params)?
+            .map_err(|error| eyre::eyre!("external evm failed to {}: {}", method, error))
+    }
+
+    /// Like [`ExternalEvm::request`], but returns the error the environment answered with as the
+    /// inner error so that it can be handled
+    fn try_request<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<std::result::Result<R, String>> {
+        let mut conn = self.connection.borrow_mut();
+        conn.next_id += 1;
+        let request = json!({ "id": conn.next_id, "method": method, "params": params });
+        writeln!(conn.stdin, "{}", request)?;
+        conn.stdin.flush()?;
+
+        let mut line = String::new();
+        if conn.stdout.read_line(&mut line)? == 0 {
+            eyre::bail!("external evm `{}` exited unexpectedly", self.command)
+        }
+        let response: Response = serde_json::from_str(&line)
+            .wrap_err_with(|| format!("invalid response from external evm: {}", line.trim()))?;
+        if response.id != Some(conn.next_id) {
+            eyre::bail!(
+                "external evm answered request {} with a response to {:?}: {}",
+                conn.next_id,
+                response.id,
+                line.trim()
+            )
+        }
+        match response.error {
+            Some(error) => Ok(Err(error)),
+            None => Ok(Ok(serde_json::from_value(response.result)?)),
+        }
+    }
+
+    /// Lets the external environment replace the compiled bytecode of the contracts with its
+    /// own. Contracts missing from the response keep their bytecode, as do all contracts if the
+    /// environment does not support compiling.
+    pub fn compile(
+        &self,
+        root: &Path,
+        contracts: &mut BTreeMap<String, (Abi, Bytes)>,
+    ) -> Result<()> {
+        let request = contracts
+            .iter()
+            .map(|(name, (abi, bytecode))| {
+                (name.clone(), json!({ "abi": abi, "bytecode": bytecode }))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        let compiled: BTreeMap<String, Bytes> =
+            match self.try_request("compile", json!({ "root": root, "contracts": request }))? {
+                Ok(compiled) => compiled,
+                Err(error) if error == METHOD_NOT_SUPPORTED => return Ok(()),
+                Err(error) => eyre::bail!("external evm failed to compile: {}", error),
+            };
+        for (name, bytecode) in compiled {
+            if let Some((_, code)) = contracts.get_mut(&name) {
+                *code = bytecode;
+            }
+        }
+        Ok(())
+    }
+
+    /// Snapshots the current state of the environment, so that [`Evm::reset`] with the returned
+    /// state reverts back to it
+    pub fn snapshot(&mut self) -> Result<ExternalState> {
+        let id = self.request("snapshot", json!({}))?;
+        self.state = ExternalState { snapshot: Some(id) };
+        Ok(self.state.clone())
+    }
+
+    fn execute(&mut self, method: &str, params: Value) -> Result<Execution> {
+        let execution: Execution = self.request(method, params)?;
+        self.logs.extend(execution.logs.iter().cloned());
+        Ok(execution)
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+impl Evm<ExternalState> for ExternalEvm {
+    type ReturnReason = ExternalExitReason;
+
+    fn revert() -> Self::ReturnReason {
+        ExternalExitReason::Revert
+    }
+
+    fn expected_revert(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn is_success(reason: &Self::ReturnReason) -> bool {
+        *reason == ExternalExitReason::Success
+    }
+
+    fn is_fail(reason: &Self::ReturnReason) -> bool {
+        *reason == ExternalExitReason::Revert
+    }
+
+    fn initialize_contracts<I: IntoIterator<Item = (Address, Bytes)>>(&mut self, contracts: I) {
+        for (address, code) in contracts {
+            if let Err(err) =
+                self.request::<Value>("set_code", json!({ "address": address, "code": code }))
+            {
+                tracing::error!(?err, ?address, "could not initialize contract");
+            }
+        }
+    }
+
+    fn state(&self) -> &ExternalState {
+        &self.state
+    }
+
+    fn code(&self, address: Address) -> Vec<u8> {
+        self.request::<Bytes>("code", json!({ "address": address }))
+            .map(|code| code.to_vec())
+            .unwrap_or_default()
+    }
+
+    fn storage(&self, address: Address, slot: H256) -> H256 {
+        self.request("storage", json!({ "address": address, "slot": slot })).unwrap_or_default()
+    }
+
+    fn set_balance(&mut self, address: Address, amount: U256) {
+        if let Err(err) =
+            self.request::<Value>("set_balance", json!({ "address": address, "amount": amount }))
+        {
+            tracing::error!(?err, ?address, "could not set balance");
+        }
+    }
+
+    fn reset(&mut self, state: ExternalState) {
+        self.logs.clear();
+        let res = match state.snapshot {
+            Some(id) => self.request::<Value>("revert", json!({ "id": id })),
+            None => self.request::<Value>("reset", json!({})),
+        };
+        if let Err(err) = res {
+            tracing::error!(?err, "could not reset the external evm");
+        }
+        self.state = state;
+    }
+
+    fn set_tracing_enabled(&mut self, _enabled: bool) -> bool {
+        false
+    }
+
+    fn tracing_enabled(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "sputnik")]
+    fn debug_calls(&self) -> Vec<DebugArena> {
+        vec![]
+    }
+
+    fn all_logs(&self) -> Vec<String> {
+        self.logs.clone()
+    }
+
+    fn call_raw(
+        &mut self,
+        from: Address,
+        to: Address,
+        calldata: Bytes,
+        value: U256,
+        is_static: bool,
+    ) -> Result<(Bytes, Self::ReturnReason, u64, Vec<String>)> {
+        let execution = self.execute(
+            "call",
+            json!({ "from": from, "to": to, "data": calldata, "value": value, "static": is_static }),
+        )?;
+        let reason = execution.reason();
+        Ok((execution.output, reason, execution.gas, execution.logs))
+    }
+
+    fn deploy(
+        &mut self,
+        from: Address,
+        calldata: Bytes,
+        value: U256,
+    ) -> Result<(Address, Self::ReturnReason, u64, Vec<String>)> {
+        let execution =
+            self.execute("deploy", json!({ "from": from, "code": calldata, "value": value }))?;
+        if !execution.success {
+            let reason =
+                foundry_utils::decode_revert(execution.output.as_ref()).unwrap_or_default();
+            return Err(EvmError::Execution {
+                reason,
+                gas_used: execution.gas,
+                logs: execution.logs,
+            }
+            .into())
+        }
+        Ok((execution.address, ExternalExitReason::Success, execution.gas, execution.logs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm_opts::EvmType;
+
+    /// A fake environment answering every request with the same response fields
+    fn fake(response: &str) -> ExternalEvm {
+        ExternalEvm::spawn(&format!(
+            r#"while read line; do id=$(echo "$line" | sed 's/^{{"id":\([0-9]*\).*/\1/'); printf '{{"id":%s,%s}}\n' "$id" '{}'; done"#,
+            response
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn can_parse_external_evm_type() {
+        match "external:zkvm --chain 280".parse::<EvmType>().unwrap() {
+            EvmType::External(cmd) => assert_eq!(cmd, "zkvm --chain 280"),
+            other => panic!("unexpected evm type {:?}", other),
+        }
+        assert!("external:".parse::<EvmType>().is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn can_execute_in_external_process() {
+        let mut evm = fake(r#""result":{"success":true,"output":"0x01","gas":42,"logs":["hi"]}"#);
+        let (output, reason, gas, logs) = evm
+            .call_raw(Address::zero(), Address::zero(), Bytes::default(), 0.into(), false)
+            .unwrap();
+        assert_eq!(output.as_ref(), &[1u8]);
+        assert_eq!(reason, ExternalExitReason::Success);
+        assert_eq!(gas, 42);
+        assert_eq!(logs, vec!["hi".to_string()]);
+        assert_eq!(evm.all_logs(), vec!["hi".to_string()]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn reports_external_errors() {
+        let mut evm = fake(r#""error":"unsupported opcode""#);
+        let err = evm.deploy(Address::zero(), Bytes::default(), 0.into()).unwrap_err();
+        assert!(err.to_string().contains("unsupported opcode"));
+
+        let mut evm = fake(r#""result":{"success":false,"output":"0x"}"#);
+        assert!(evm.deploy(Address::zero(), Bytes::default(), 0.into()).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compiling_is_optional() {
+        let abi: Abi = serde_json::from_str("[]").unwrap();
+        let mut contracts = BTreeMap::new();
+        contracts.insert("Foo".to_string(), (abi, Bytes::from(vec![1u8])));
+
+        let evm = fake(r#""error":"method not supported""#);
+        evm.compile(Path::new("."), &mut contracts).unwrap();
+        assert_eq!(contracts["Foo"].1, Bytes::from(vec![1u8]));
+
+        let evm = fake(r#""result":{"Foo":"0x02","Bar":"0x03"}"#);
+        evm.compile(Path::new("."), &mut contracts).unwrap();
+        assert_eq!(contracts["Foo"].1, Bytes::from(vec![2u8]));
+        assert!(!contracts.contains_key("Bar"));
+
+        let evm = fake(r#""error":"no compiler found""#);
+        let err = evm.compile(Path::new("."), &mut contracts).unwrap_err();
+        assert!(err.to_string().contains("no compiler found"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn rejects_mismatched_response_ids() {
+        let evm = ExternalEvm::spawn(r#"while read line; do echo '{"id":7,"result":"0x"}'; done"#)
+            .unwrap();
+        let err = evm.request::<Bytes>("code", json!({ "address": Address::zero() })).unwrap_err();
+        assert!(err.to_string().contains("answered request 1"));
+    }
+}
@@ -7,6 +7,7 @@ use ethers::{
 use std::{
     cell::{RefCell, RefMut},
     marker::PhantomData,
+    time::Instant,
 };
 
 pub use proptest::test_runner::Config as FuzzConfig;
@@ -16,6 +17,10 @@ use proptest::{
 };
 use serde::{Deserialize, Serialize};
 
+/// The number of cases run at a time by a time-bounded fuzz test, before checking whether its
+/// time is up
+pub const FUZZ_TIME_CHUNK: u32 = 64;
+
 /// Wrapper around any [`Evm`](crate::Evm) implementor which provides fuzzing support using [`proptest`](https://docs.rs/proptest/1.0.0/proptest/).
 ///
 /// After instantiation, calling `fuzz` will proceed to hammer the deployed smart contract with
@@ -27,6 +32,10 @@ pub struct FuzzedExecutor<'a, E, S> {
     runner: TestRunner,
     state: PhantomData<S>,
     sender: Address,
+    /// If set, cases are run until this instant instead of for the configured number of cases
+    run_until: Option<Instant>,
+    /// If set, no more cases are run after this instant
+    deadline: Option<Instant>,
 }
 
 impl<'a, S, E: Evm<S>> FuzzedExecutor<'a, E, S> {
@@ -41,7 +50,29 @@ impl<'a, S, E: Evm<S>> FuzzedExecutor<'a, E, S> {
 
     /// Instantiates a fuzzed executor EVM given a testrunner
     pub fn new(evm: &'a mut E, runner: TestRunner, sender: Address) -> Self {
-        Self { evm: RefCell::new(evm), runner, state: PhantomData, sender }
+        Self {
+            evm: RefCell::new(evm),
+            runner,
+            state: PhantomData,
+            sender,
+            run_until: None,
+            deadline: None,
+        }
+    }
+
+    /// Runs cases until the instant instead of for the configured number of cases
+    #[must_use]
+    pub fn run_until(mut self, run_until: Option<Instant>) -> Self {
+        self.run_until = run_until;
+        self
+    }
+
+    /// Stops running cases once the deadline passed, checked every [`FUZZ_TIME_CHUNK`] cases. No
+    /// cases are run if the deadline already passed.
+    #[must_use]
+    pub fn deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
     }
 
     /// Fuzzes the provided function, assuming it is available at the contract at `address`
@@ -74,54 +105,75 @@ impl<'a, S, E: Evm<S>> FuzzedExecutor<'a, E, S> {
         let return_reason: RefCell<Option<E::ReturnReason>> = RefCell::new(None);
         let revert_reason = RefCell::new(None);
 
-        let mut runner = self.runner.clone();
         tracing::debug!(func = ?func.name, should_fail, "fuzzing");
-        let test_error = runner
-            .run(&strat, |calldata| {
-                let mut evm = self.evm.borrow_mut();
-                // Before each test, we must reset to the initial state
-                evm.reset(pre_test_state.clone());
-
-                let (returndata, reason, gas, _) = evm
-                    .call_raw(self.sender, address, calldata.clone(), 0.into(), false)
-                    .expect("could not make raw evm call");
-
-                // We must check success before resetting the state, otherwise resetting the state
-                // will also reset the `failed` state variable back to false.
-                let success = evm.check_success(address, &reason, should_fail);
-
-                // store the result of this test case
-                let _ = return_reason.borrow_mut().insert(reason);
-
-                if !success {
-                    let revert =
-                        foundry_utils::decode_revert(returndata.as_ref()).unwrap_or_default();
-                    let _ = revert_reason.borrow_mut().insert(revert);
+        let test = |calldata: Bytes| {
+            let mut evm = self.evm.borrow_mut();
+            // Before each test, we must reset to the initial state
+            evm.reset(pre_test_state.clone());
+
+            let (returndata, reason, gas, _) = evm
+                .call_raw(self.sender, address, calldata.clone(), 0.into(), false)
+                .expect("could not make raw evm call");
+
+            // We must check success before resetting the state, otherwise resetting the state
+            // will also reset the `failed` state variable back to false.
+            let success = evm.check_success(address, &reason, should_fail);
+
+            // store the result of this test case
+            let _ = return_reason.borrow_mut().insert(reason);
+
+            if !success {
+                let revert = foundry_utils::decode_revert(returndata.as_ref()).unwrap_or_default();
+                let _ = revert_reason.borrow_mut().insert(revert);
+            }
+
+            // This will panic and get caught by the executor
+            proptest::prop_assert!(
+                success,
+                "{}, expected failure: {}, reason: '{}'",
+                func.name,
+                should_fail,
+                match foundry_utils::decode_revert(returndata.as_ref()) {
+                    Ok(e) => e,
+                    Err(e) => e.to_string(),
                 }
-
-                // This will panic and get caught by the executor
-                proptest::prop_assert!(
-                    success,
-                    "{}, expected failure: {}, reason: '{}'",
-                    func.name,
-                    should_fail,
-                    match foundry_utils::decode_revert(returndata.as_ref()) {
-                        Ok(e) => e,
-                        Err(e) => e.to_string(),
+            );
+
+            // push test case to the case set
+            fuzz_cases.borrow_mut().push(FuzzCase { calldata, gas });
+
+            Ok(())
+        };
+
+        let test_error = match self.run_until.into_iter().chain(self.deadline).min() {
+            None => self.runner.clone().run(&strat, test).err(),
+            Some(deadline) => {
+                // run chunks of cases until the time is up, each with a new rng derived from the
+                // configured runner's so that the chunks fuzz different inputs. Without a time to
+                // run until, the time only caps the configured number of cases
+                let mut base = self.runner.clone();
+                let mut remaining = self.run_until.map_or(base.config().cases, |_| u32::MAX);
+                loop {
+                    if remaining == 0 || Instant::now() >= deadline {
+                        break None
                     }
-                );
-
-                // push test case to the case set
-                fuzz_cases.borrow_mut().push(FuzzCase { calldata, gas });
-
-                Ok(())
-            })
-            .err()
-            .map(|test_error| FuzzError {
-                test_error,
-                return_reason: return_reason.into_inner().expect("Reason must be set"),
-                revert_reason: revert_reason.into_inner().expect("Revert error string must be set"),
-            });
+                    let cases = remaining.min(FUZZ_TIME_CHUNK);
+                    let config = FuzzConfig { cases, ..base.config().clone() };
+                    let mut runner = TestRunner::new_with_rng(config, base.new_rng());
+                    if let Err(err) = runner.run(&strat, &test) {
+                        break Some(err)
+                    }
+                    if self.run_until.is_none() {
+                        remaining -= cases;
+                    }
+                }
+            }
+        }
+        .map(|test_error| FuzzError {
+            test_error,
+            return_reason: return_reason.into_inner().expect("Reason must be set"),
+            revert_reason: revert_reason.into_inner().expect("Revert error string must be set"),
+        });
 
         FuzzTestResult { cases: FuzzedCases::new(fuzz_cases.into_inner()), test_error }
     }
@@ -308,6 +360,7 @@ mod tests {
         test_helpers::COMPILED,
         Evm,
     };
+    use std::time::Duration;
 
     #[test]
     fn prints_fuzzed_revert_reasons() {
@@ -325,4 +378,56 @@ mod tests {
         let revert_reason = error.revert_reason;
         assert_eq!(revert_reason, "fuzztest-revert");
     }
+
+    #[test]
+    fn deadline_caps_fuzz_cases() {
+        let mut evm = vm();
+
+        let compiled = COMPILED.find("FuzzTests").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        // no cases are run after the deadline
+        let evm = fuzzvm(&mut evm).deadline(Some(Instant::now()));
+
+        let func = compiled.abi.unwrap().function("testFuzzedPass").unwrap();
+        let res = evm.fuzz(&func, addr, false);
+        assert!(res.is_ok());
+        assert!(res.cases.cases().is_empty());
+    }
+
+    #[test]
+    fn deadline_keeps_configured_fuzz_cases() {
+        let mut evm = vm();
+
+        let compiled = COMPILED.find("FuzzTests").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(600);
+        let evm = fuzzvm(&mut evm).deadline(Some(deadline));
+
+        let func = compiled.abi.unwrap().function("testFuzzedPass").unwrap();
+        let res = evm.fuzz(&func, addr, false);
+        assert!(res.is_ok());
+        assert_eq!(res.cases.cases().len(), FuzzConfig::default().cases as usize);
+    }
+
+    #[test]
+    fn fuzzes_until_time() {
+        let mut evm = vm();
+
+        let compiled = COMPILED.find("FuzzTests").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let run_until = Instant::now() + Duration::from_millis(100);
+        let evm = fuzzvm(&mut evm).run_until(Some(run_until));
+
+        let func = compiled.abi.unwrap().function("testFuzzedPass").unwrap();
+        let res = evm.fuzz(&func, addr, false);
+        assert!(res.is_ok());
+        assert!(Instant::now() >= run_until);
+        assert_eq!(res.cases.cases().len() % FUZZ_TIME_CHUNK as usize, 0);
+    }
 }
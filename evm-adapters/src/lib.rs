@@ -18,13 +18,25 @@ pub mod fuzz;
 
 pub mod call_tracing;
 
+/// Export of call traces in the formats of other tools
+pub mod trace_format;
+
+/// Selector and event topic index used for decoding traces
+pub mod signatures;
+
 /// Helpers for easily constructing EVM objects.
 pub mod evm_opts;
 
+/// Execution environments running in an external process
+pub mod external;
+
+/// Changes made to the state by an execution
+pub mod state_diff;
+
 use ethers::{
     abi::{Detokenize, Tokenize},
     contract::{decode_function_data, encode_function_data},
-    core::types::{Address, Bytes, U256},
+    core::types::{Address, Bytes, H256, U256},
 };
 
 use foundry_utils::IntoFunction;
@@ -76,6 +88,12 @@ pub trait Evm<State> {
 
     fn code(&self, address: Address) -> Vec<u8>;
 
+    /// Reads a storage slot of the specified address. EVMs which do not expose their storage
+    /// return an empty slot.
+    fn storage(&self, _address: Address, _slot: H256) -> H256 {
+        H256::zero()
+    }
+
     /// Sets the balance at the specified address
     fn set_balance(&mut self, address: Address, amount: U256);
 
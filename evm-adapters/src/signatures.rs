@@ -0,0 +1,185 @@
+use ethers::{
+    abi::{Abi, Event, Function},
+    types::H256,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// An index of function selectors and event topics to their ABI definitions, used to decode
+/// traces without having to re-hash every ABI entry for every call.
+///
+/// The cache is filled from the project's artifacts and can be persisted to disk so that
+/// selectors seen in previous runs (e.g. of contracts which have since been removed, or
+/// signatures added from remote lookups) can still be decoded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SignatureCache {
+    /// Function selector (`0x` prefixed hex) => function
+    functions: BTreeMap<String, Function>,
+    /// Event topic (`0x` prefixed hex) => event
+    events: BTreeMap<String, Event>,
+}
+
+impl SignatureCache {
+    /// Loads a cache from the given json file, returning an empty cache if the file does not
+    /// exist or could not be parsed
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| match serde_json::from_str(&content) {
+                Ok(cache) => Some(cache),
+                Err(err) => {
+                    tracing::warn!(?err, "failed to read signature cache {}", path.display());
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache as json to the given file, creating the parent directories if needed
+    pub fn save(&self, path: impl AsRef<Path>) -> eyre::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Indexes all functions and events of the abi
+    pub fn extend_from_abi(&mut self, abi: &Abi) {
+        abi.functions().for_each(|func| self.insert_function(func.clone()));
+        abi.events().for_each(|event| self.insert_event(event.clone()));
+    }
+
+    /// Indexes the function by its selector
+    pub fn insert_function(&mut self, func: Function) {
+        self.functions.entry(selector_key(&func.short_signature())).or_insert(func);
+    }
+
+    /// Indexes the event by its topic
+    pub fn insert_event(&mut self, event: Event) {
+        self.events.entry(topic_key(&event.signature())).or_insert(event);
+    }
+
+    /// Returns the function matching the selector of the provided calldata, if any
+    pub fn function(&self, calldata: &[u8]) -> Option<&Function> {
+        if calldata.len() < 4 {
+            return None
+        }
+        self.functions.get(&selector_key(&calldata[..4]))
+    }
+
+    /// Returns the event matching the given topic, if any
+    pub fn event(&self, topic: &H256) -> Option<&Event> {
+        self.events.get(&topic_key(topic))
+    }
+
+    /// Returns the function of the abi matching the selector of the provided calldata, if any.
+    ///
+    /// The index is consulted first, so the abi's functions only have to be hashed if the
+    /// selector is indexed for a function which is not part of the abi, i.e. on a collision.
+    pub fn abi_function<'a>(&'a self, abi: &'a Abi, calldata: &[u8]) -> Option<&'a Function> {
+        if calldata.len() < 4 {
+            return None
+        }
+        match self.function(calldata) {
+            Some(func) if abi.functions.get(&func.name).map_or(false, |f| f.contains(func)) => {
+                Some(func)
+            }
+            _ => abi.functions().find(|func| func.short_signature() == calldata[..4]),
+        }
+    }
+
+    /// Returns the event of the abi matching the given topic, if any.
+    ///
+    /// Like [`Self::abi_function`], the abi's events are only hashed if the index has no event of
+    /// the abi for the topic.
+    pub fn abi_event<'a>(&'a self, abi: &'a Abi, topic: &H256) -> Option<&'a Event> {
+        match self.event(topic) {
+            Some(event) if abi.events.get(&event.name).map_or(false, |e| e.contains(event)) => {
+                Some(event)
+            }
+            _ => abi.events().find(|event| event.signature() == *topic),
+        }
+    }
+
+    /// Returns the number of indexed functions and events
+    pub fn len(&self) -> usize {
+        self.functions.len() + self.events.len()
+    }
+
+    /// Returns true if neither functions nor events are indexed
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.events.is_empty()
+    }
+}
+
+fn selector_key(selector: &[u8]) -> String {
+    format!("0x{}", hex::encode(selector))
+}
+
+fn topic_key(topic: &H256) -> String {
+    format!("0x{}", hex::encode(topic.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::parse_abi;
+
+    #[test]
+    fn can_lookup_and_persist_signatures() {
+        let abi = parse_abi(&[
+            "function transfer(address to, uint256 amount) external returns (bool)",
+            "event Transfer(address indexed from, address indexed to, uint256 amount)",
+        ])
+        .unwrap();
+
+        let mut cache = SignatureCache::default();
+        cache.extend_from_abi(&abi);
+        assert_eq!(cache.len(), 2);
+
+        let calldata = hex::decode("a9059cbb0000").unwrap();
+        assert_eq!(cache.function(&calldata).unwrap().name, "transfer");
+        assert!(cache.function(&calldata[..3]).is_none());
+
+        let topic = abi.event("Transfer").unwrap().signature();
+        assert_eq!(cache.event(&topic).unwrap().name, "Transfer");
+
+        let dir = std::env::temp_dir().join("foundry-signature-cache-test");
+        let path = dir.join("signatures.json");
+        cache.save(&path).unwrap();
+        let loaded = SignatureCache::load(&path);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.function(&calldata).unwrap().name, "transfer");
+
+        assert!(SignatureCache::load(dir.join("missing.json")).is_empty());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn prefers_the_abi_on_collisions() {
+        let abi = parse_abi(&[
+            "function transfer(address to, uint256 amount) external returns (bool)",
+            "event Transfer(address indexed from, address indexed to, uint256 amount)",
+        ])
+        .unwrap();
+        let calldata = hex::decode("a9059cbb0000").unwrap();
+        let topic = abi.event("Transfer").unwrap().signature();
+
+        // indexed functions and events of the abi are returned from the index
+        let mut cache = SignatureCache::default();
+        cache.extend_from_abi(&abi);
+        assert_eq!(cache.abi_function(&abi, &calldata), abi.function("transfer").ok());
+        assert_eq!(cache.abi_event(&abi, &topic), abi.event("Transfer").ok());
+
+        // a colliding entry of another contract is not used for this abi
+        let mut cache = SignatureCache::default();
+        let mut other = abi.function("transfer").unwrap().clone();
+        other.outputs.clear();
+        cache.insert_function(other);
+        assert_eq!(cache.abi_function(&abi, &calldata), abi.function("transfer").ok());
+        assert!(cache.abi_function(&abi, &calldata[..3]).is_none());
+    }
+}
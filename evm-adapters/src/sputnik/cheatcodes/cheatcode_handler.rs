@@ -1,17 +1,24 @@
 //! Hooks to EVM execution
 use super::{
-    backend::CheatcodeBackend, memory_stackstate_owned::MemoryStackStateOwned, ConsoleCalls,
-    HEVMCalls, HevmConsoleEvents,
+    backend::CheatcodeBackend, memory_stackstate_owned::MemoryStackStateOwned, HEVMCalls,
+    HevmConsoleEvents,
 };
 use crate::{
     call_tracing::{CallTrace, CallTraceArena, LogCallOrder},
-    sputnik::{cheatcodes::memory_stackstate_owned::ExpectedEmit, Executor, SputnikExecutor},
+    sputnik::{
+        cheatcodes::memory_stackstate_owned::{ExpectedCall, ExpectedEmit},
+        Executor, SputnikExecutor,
+    },
     Evm,
 };
 use std::collections::BTreeMap;
 
 use serde::Deserialize;
-use std::{fs::File, io::Read, path::Path};
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use sputnik::{
     backend::Backend,
@@ -19,8 +26,8 @@ use sputnik::{
         Log, PrecompileFailure, PrecompileOutput, PrecompileSet, StackExecutor, StackExitKind,
         StackState, StackSubstateMetadata,
     },
-    gasometer, Capture, Config, Context, CreateScheme, ExitError, ExitReason, ExitRevert,
-    ExitSucceed, Handler, Memory, Opcode, Runtime, Transfer,
+    gasometer, CallScheme, Capture, Config, Context, CreateScheme, ExitError, ExitReason,
+    ExitRevert, ExitSucceed, Handler, Memory, Opcode, Runtime, Transfer,
 };
 use std::{process::Command, rc::Rc};
 
@@ -38,8 +45,9 @@ use std::{convert::Infallible, str::FromStr};
 
 use crate::sputnik::cheatcodes::{
     debugger::{CheatOp, DebugArena, DebugNode, DebugStep, OpCode},
+    format_console_log,
+    json::{self, FileAccess},
     memory_stackstate_owned::Prank,
-    patch_hardhat_console_log_selector,
 };
 use once_cell::sync::Lazy;
 
@@ -105,6 +113,8 @@ pub static DUMMY_OUTPUT: [u8; 320] = [0u8; 320];
 pub struct CheatcodeHandler<H> {
     handler: H,
     enable_ffi: bool,
+    /// Where the file cheatcodes may read and write
+    file_access: FileAccess,
     enable_trace: bool,
     console_logs: Vec<String>,
 }
@@ -203,6 +213,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> SputnikExecutor<CheatcodeStackState<'
     ) -> (ExitReason, Vec<u8>) {
         // reset all_logs because its a new call
         self.state_mut().all_logs = vec![];
+        let gas_before = self.gas_left();
 
         let transaction_cost = gasometer::call_transaction_cost(&data, &access_list);
         match self.state_mut().metadata_mut().gasometer_mut().record_transaction(transaction_cost) {
@@ -234,20 +245,49 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> SputnikExecutor<CheatcodeStackState<'
         ) {
             Capture::Exit((s, v)) => {
                 self.state_mut().increment_call_index();
+                let gas_budget = self.state_mut().gas_budget.take();
 
                 // check if all expected calls were made
-                if let Some((address, expecteds)) =
-                    self.state().expected_calls.iter().find(|(_, expecteds)| !expecteds.is_empty())
+                let expected_calls = std::mem::take(&mut self.state_mut().expected_calls);
+                if let Some((address, expected)) =
+                    expected_calls.iter().find_map(|(address, expecteds)| {
+                        expecteds
+                            .iter()
+                            .find(|expected| !expected.is_satisfied())
+                            .map(|expected| (address, expected))
+                    })
                 {
+                    let data = ethers::types::Bytes::from(expected.data.clone());
+                    let reason = match expected.count {
+                        Some(count) => format!(
+                            "Expected a call to 0x{} with data {} to be made {} time(s), but got {}",
+                            address, data, count, expected.found
+                        ),
+                        None => {
+                            format!("Expected a call to 0x{} with data {}, but got none", address, data)
+                        }
+                    };
                     return (
                         ExitReason::Revert(ExitRevert::Reverted),
-                        ethers::abi::encode(&[Token::String(format!(
-                            "Expected a call to 0x{} with data {}, but got none",
-                            address,
-                            ethers::types::Bytes::from(expecteds[0].clone())
-                        ))]),
+                        ethers::abi::encode(&[Token::String(reason)]),
                     )
                 }
+
+                // check that the call stayed within its gas budget, measured like the gas
+                // reported for the call, i.e. without the base transaction cost
+                if let Some(budget) = gas_budget {
+                    let gas_used =
+                        gas_before.saturating_sub(self.gas_left()).saturating_sub(21000.into());
+                    if gas_used > budget.into() {
+                        return (
+                            ExitReason::Revert(ExitRevert::Reverted),
+                            ethers::abi::encode(&[Token::String(format!(
+                                "Gas budget exceeded: used {} gas, but the budget is {}",
+                                gas_used, budget
+                            ))]),
+                        )
+                    }
+                }
                 (s, v)
             }
             Capture::Trap(_) => {
@@ -340,6 +380,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet>
         config: &'a Config,
         precompiles: &'b P,
         enable_ffi: bool,
+        file_access: FileAccess,
         enable_trace: bool,
         debug: bool,
     ) -> Self {
@@ -356,6 +397,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet>
         let executor = CheatcodeHandler {
             handler: executor,
             enable_ffi,
+            file_access,
             enable_trace,
             console_logs: Vec::new(),
         };
@@ -373,6 +415,20 @@ impl<'a, 'b, B: Backend, P: PrecompileSet>
     }
 }
 
+// sputnik's handler is not told the scheme of a call, recover it from its arguments: only `CALL`
+// and `CALLCODE` transfer value, `CALLCODE` to the caller itself which runs the callee's code, and
+// `DELEGATECALL` runs the callee's code in the caller's context without a transfer
+fn call_scheme(code_address: H160, transfer: &Option<Transfer>, context: &Context) -> CallScheme {
+    match transfer {
+        Some(transfer) if transfer.target == context.address && context.address != code_address => {
+            CallScheme::CallCode
+        }
+        Some(_) => CallScheme::Call,
+        None if context.address != code_address => CallScheme::DelegateCall,
+        None => CallScheme::StaticCall,
+    }
+}
+
 // helper for creating an exit type
 fn evm_error(retdata: &str) -> Capture<(ExitReason, Vec<u8>), Infallible> {
     Capture::Exit((
@@ -482,20 +538,46 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
         }
     }
 
-    /// Given a transaction's calldata, it tries to parse it a console call and print the call
+    /// Given a transaction's calldata, it tries to parse it a console call and print the call.
+    ///
+    /// Like on a live network, where the console address has no code, the call never reverts:
+    /// calls which cannot be decoded are logged as raw calldata.
     fn console_log(&mut self, input: Vec<u8>) -> Capture<(ExitReason, Vec<u8>), Infallible> {
-        // replacing hardhat style selectors (`uint`) with abigen style (`uint256`)
-        let input = patch_hardhat_console_log_selector(input);
-        let decoded = match ConsoleCalls::decode(&input) {
-            Ok(inner) => inner,
-            Err(err) => return evm_error(&err.to_string()),
-        };
-        self.console_logs.push(decoded.to_string());
+        let log = format_console_log(&input).unwrap_or_else(|err| {
+            format!("console.log: {} (calldata 0x{})", err, hex::encode(&input))
+        });
+        self.console_logs.push(log);
         Capture::Exit((ExitReason::Succeed(ExitSucceed::Stopped), vec![]))
     }
 
+    /// Sets a value of a JSON object built with the `serialize*` cheatcodes and returns the
+    /// ABI-encoded serialized object
+    fn serialize_json(
+        &mut self,
+        object_key: &str,
+        value_key: &str,
+        value: serde_json::Value,
+    ) -> Vec<u8> {
+        let serialized =
+            json::serialize(&mut self.state_mut().serialized_jsons, object_key, value_key, value);
+        ethers::abi::encode(&[Token::String(serialized)])
+    }
+
     /// Adds CheatOp to the latest DebugArena
     fn add_debug(&mut self, cheatop: CheatOp) {
+        self.add_debug_step(DebugStep { op: OpCode::from(cheatop), ..Default::default() })
+    }
+
+    /// Adds a step labeled with the breakpoint, so that the debugger can jump to it
+    fn add_breakpoint(&mut self, label: String) {
+        self.add_debug_step(DebugStep {
+            op: OpCode::from(CheatOp::BREAKPOINT),
+            breakpoint: Some(label),
+            ..Default::default()
+        })
+    }
+
+    fn add_debug_step(&mut self, step: DebugStep) {
         if self.state().debug_enabled {
             let depth =
                 if let Some(depth) = self.state().metadata().depth() { depth + 1 } else { 0 };
@@ -504,11 +586,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
                 DebugNode {
                     address: *CHEATCODE_ADDRESS,
                     depth,
-                    steps: vec![DebugStep {
-                        op: OpCode::from(cheatop),
-                        memory: Memory::new(0),
-                        ..Default::default()
-                    }],
+                    steps: vec![DebugStep { memory: Memory::new(0), ..step }],
                     ..Default::default()
                 },
             );
@@ -817,9 +895,75 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
                 self.add_debug(CheatOp::CLEARMOCKEDCALLS);
                 self.state_mut().mocked_calls = Default::default();
             }
-            HEVMCalls::ExpectCall(inner) => {
+            HEVMCalls::ExpectCall0(inner) => {
+                self.add_debug(CheatOp::EXPECTCALL);
+                self.state_mut()
+                    .expected_calls
+                    .entry(inner.0)
+                    .or_default()
+                    .push(ExpectedCall { data: inner.1.to_vec(), ..Default::default() });
+            }
+            HEVMCalls::ExpectCall1(inner) => {
                 self.add_debug(CheatOp::EXPECTCALL);
-                self.state_mut().expected_calls.entry(inner.0).or_default().push(inner.1.to_vec());
+                self.state_mut().expected_calls.entry(inner.0).or_default().push(ExpectedCall {
+                    data: inner.1.to_vec(),
+                    count: Some(inner.2),
+                    found: 0,
+                });
+            }
+            HEVMCalls::Breakpoint(inner) => {
+                self.add_breakpoint(inner.0);
+            }
+            HEVMCalls::ExpectMaxGas(inner) => {
+                self.add_debug(CheatOp::EXPECTMAXGAS);
+                self.state_mut().gas_budget = Some(inner.0);
+            }
+            HEVMCalls::ReadFile(inner) => {
+                self.add_debug(CheatOp::READFILE);
+                let contents = match self
+                    .file_access
+                    .resolve(&inner.0)
+                    .and_then(|path| Ok(std::fs::read_to_string(path)?))
+                {
+                    Ok(contents) => contents,
+                    Err(err) => return evm_error(&err.to_string()),
+                };
+                res = ethers::abi::encode(&[Token::String(contents)]);
+            }
+            HEVMCalls::ParseJson(inner) => {
+                self.add_debug(CheatOp::PARSEJSON);
+                let encoded = match json::parse_json(&inner.0, &inner.1) {
+                    Ok(encoded) => encoded,
+                    Err(err) => return evm_error(&err.to_string()),
+                };
+                res = ethers::abi::encode(&[Token::Bytes(encoded)]);
+            }
+            HEVMCalls::SerializeUint(inner) => {
+                self.add_debug(CheatOp::SERIALIZE);
+                res = self.serialize_json(&inner.0, &inner.1, json::uint_value(inner.2));
+            }
+            HEVMCalls::SerializeAddress(inner) => {
+                self.add_debug(CheatOp::SERIALIZE);
+                let value = serde_json::Value::String(utils::to_checksum(&inner.2, None));
+                res = self.serialize_json(&inner.0, &inner.1, value);
+            }
+            HEVMCalls::SerializeBool(inner) => {
+                self.add_debug(CheatOp::SERIALIZE);
+                res = self.serialize_json(&inner.0, &inner.1, serde_json::Value::Bool(inner.2));
+            }
+            HEVMCalls::SerializeString(inner) => {
+                self.add_debug(CheatOp::SERIALIZE);
+                res = self.serialize_json(&inner.0, &inner.1, json::string_value(inner.2));
+            }
+            HEVMCalls::WriteJson(inner) => {
+                self.add_debug(CheatOp::WRITEJSON);
+                if let Err(err) = self
+                    .file_access
+                    .resolve(&inner.1)
+                    .and_then(|path| json::write_json(&inner.0, &path))
+                {
+                    return evm_error(&err.to_string())
+                }
             }
         };
 
@@ -893,6 +1037,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
                 push_bytes,
                 ic: *pc_ic.get(&pc).as_ref().copied().unwrap_or(&0usize),
                 total_gas_used: self.handler.used_gas(),
+                breakpoint: None,
             });
             match op {
                 Opcode::CREATE |
@@ -920,6 +1065,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
                 push_bytes,
                 ic: *pc_ic.get(&pc).as_ref().copied().unwrap_or(&0usize),
                 total_gas_used: self.handler.used_gas(),
+                breakpoint: None,
             });
             true
         }
@@ -1087,6 +1233,13 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
             transfer.as_ref().map(|x| x.value).unwrap_or_default(),
             false,
         );
+        // delegatecalls execute the code in the caller's context, like callcodes which however
+        // are not marked since they change `msg.sender` and `msg.value`
+        if let Some(ref trace) = trace {
+            if call_scheme(code_address, &transfer, &context) == CallScheme::DelegateCall {
+                self.state_mut().trace_mut().arena[trace.idx].trace.delegate = true;
+            }
+        }
 
         macro_rules! try_or_fail {
             ( $e:expr ) => {
@@ -1478,12 +1631,17 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> Handler for CheatcodeStackExecutor<'a
                 self.state_mut().backend.cheats.origin = new_origin;
             }
 
-            // handle expected calls
+            // handle expected calls: a call counts towards all matching expectations with a
+            // count, and satisfies the first matching one without a count which was not met yet
             if let Some(expecteds) = self.state_mut().expected_calls.get_mut(&code_address) {
-                if let Some(found_match) =
-                    expecteds.iter().position(|expected| expected == &input[..expected.len()])
-                {
-                    expecteds.remove(found_match);
+                let mut satisfied = false;
+                for expected in expecteds.iter_mut().filter(|e| input.starts_with(&e.data)) {
+                    if expected.count.is_some() {
+                        expected.found += 1;
+                    } else if !satisfied && expected.found == 0 {
+                        expected.found += 1;
+                        satisfied = true;
+                    }
                 }
             }
 
@@ -1761,6 +1919,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> Handler for CheatcodeStackExecutor<'a
 mod tests {
     use crate::{
         fuzz::FuzzedExecutor,
+        signatures::SignatureCache,
         sputnik::helpers::{vm, vm_no_limit, vm_tracing},
         test_helpers::COMPILED,
         Evm,
@@ -1824,10 +1983,10 @@ mod tests {
         let expected = [
             "0x1111111111111111111111111111111111111111",
             "Hi",
-            "Hi, Hi",
+            "Hi Hi",
             "1337",
-            "1337, 1245",
-            "Hi, 1337",
+            "1337 1245",
+            "Hi 1337",
         ]
         .iter()
         .map(ToString::to_string)
@@ -1857,6 +2016,28 @@ mod tests {
         assert_eq!(logs, expected);
     }
 
+    #[test]
+    fn console_logs_format() {
+        let mut evm = vm();
+
+        let compiled = COMPILED.find("ConsoleLogs").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let (_, _, _, logs) =
+            evm.call::<(), _, _>(Address::zero(), addr, "test_log_format()", (), 0.into()).unwrap();
+        let expected = [
+            "alice has 100 tokens",
+            "0x1111111111111111111111111111111111111111 sent true",
+            "100% done",
+            "0xbeef",
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+        assert_eq!(logs, expected);
+    }
+
     #[test]
     fn logs_external_contract() {
         let mut evm = vm();
@@ -1879,6 +2060,7 @@ mod tests {
     #[test]
     fn cheatcodes() {
         let mut evm = vm_no_limit();
+        evm.executor.file_access.allowed_paths = vec![PathBuf::from("./testdata")];
         let compiled = COMPILED.find("CheatCodes").expect("could not find contract");
         let (addr, _, _, _) =
             evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
@@ -1919,6 +2101,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn records_breakpoints() {
+        use crate::sputnik::{
+            cheatcodes::debugger::breakpoints,
+            helpers::{new_backend, CFG_NO_LMT, GAS_LIMIT, VICINITY},
+            PRECOMPILES_MAP,
+        };
+
+        let backend = new_backend(&*VICINITY, Default::default());
+        let mut evm = Executor::new_with_cheatcodes(
+            backend,
+            GAS_LIMIT,
+            &*CFG_NO_LMT,
+            &*PRECOMPILES_MAP,
+            false,
+            Default::default(),
+            false,
+            true,
+        );
+        let compiled = COMPILED.find("CheatCodes").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+        evm.call::<(), _, _>(Address::zero(), addr, "testBreakpoint()", (), 0.into()).unwrap();
+
+        let calls = evm.debug_calls();
+        let mut flattened = Vec::new();
+        calls.last().expect("no debug calls").flatten(0, &mut flattened);
+        let found = breakpoints(&flattened);
+        assert_eq!(
+            found.iter().map(|(label, _, _)| label.as_str()).collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+
+        // every breakpoint points at the step of the cheatcode call which set it
+        for (label, call, step) in &found {
+            let (address, steps, _) = &flattened[*call];
+            assert_eq!(*address, *CHEATCODE_ADDRESS);
+            assert_eq!(steps[*step].breakpoint.as_ref(), Some(label));
+            assert_eq!(steps[*step].pretty_opcode(), format!("VM_BREAKPOINT({})", label));
+        }
+        assert!(found[0].1 < found[1].1);
+    }
+
     #[test]
     fn ffi_fails_if_disabled() {
         let mut evm = vm_no_limit();
@@ -1991,7 +2216,7 @@ mod tests {
             ),
         );
         let mut identified = Default::default();
-        evm.traces()[1].pretty_print(0, &mapping, &mut identified, &evm, "");
+        evm.traces()[1].pretty_print(0, &mapping, &Default::default(), &mut identified, &evm, "");
     }
 
     #[test]
@@ -2048,7 +2273,88 @@ mod tests {
                     .to_vec(),
             ),
         );
+        let mut signatures = SignatureCache::default();
+        mapping.values().for_each(|(abi, _)| signatures.extend_from_abi(abi));
         let mut identified = Default::default();
-        evm.traces()[1].pretty_print(0, &mapping, &mut identified, &evm, "");
+        evm.traces()[1].pretty_print(0, &mapping, &signatures, &mut identified, &evm, "");
+    }
+
+    #[test]
+    fn recovers_call_schemes() {
+        let (caller, callee) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let context = |address| Context { address, caller, apparent_value: 0u64.into() };
+        let transfer = |target| Some(Transfer { source: caller, target, value: 0u64.into() });
+
+        assert_eq!(call_scheme(callee, &transfer(callee), &context(callee)), CallScheme::Call);
+        assert_eq!(call_scheme(caller, &transfer(caller), &context(caller)), CallScheme::Call);
+        assert_eq!(call_scheme(callee, &transfer(caller), &context(caller)), CallScheme::CallCode);
+        assert_eq!(call_scheme(callee, &None, &context(caller)), CallScheme::DelegateCall);
+        assert_eq!(call_scheme(callee, &None, &context(callee)), CallScheme::StaticCall);
+    }
+
+    #[test]
+    fn tracing_proxy() {
+        use std::collections::BTreeMap;
+
+        let mut evm = vm_tracing(false);
+
+        let implementation = COMPILED.find("ProxyImpl").expect("could not find contract");
+        let (impl_addr, _, _, _) = evm
+            .deploy(
+                Address::zero(),
+                implementation.bin.unwrap().clone().into_bytes().expect("shouldn't be linked"),
+                0.into(),
+            )
+            .unwrap();
+
+        let proxy = COMPILED.find("Proxy").expect("could not find contract");
+        let mut code =
+            proxy.bin.unwrap().clone().into_bytes().expect("shouldn't be linked").to_vec();
+        code.extend_from_slice(&ethers::abi::encode(&[ethers::abi::Token::Address(impl_addr)]));
+        let (proxy_addr, _, _, _) = evm.deploy(Address::zero(), code.into(), 0.into()).unwrap();
+
+        evm.call::<(), _, _>(
+            Address::zero(),
+            proxy_addr,
+            "setNum(uint256)",
+            (U256::from(7u32),),
+            0u32.into(),
+        )
+        .unwrap();
+
+        let mut mapping = BTreeMap::new();
+        for (name, compiled) in [("Proxy", proxy), ("ProxyImpl", implementation)] {
+            mapping.insert(
+                name.to_string(),
+                (
+                    compiled.abi.expect("No abi").clone(),
+                    compiled
+                        .bin_runtime
+                        .expect("No runtime")
+                        .clone()
+                        .into_bytes()
+                        .expect("Linking?")
+                        .to_vec(),
+                ),
+            );
+        }
+
+        let traces = evm.traces();
+        let trace = traces.last().expect("no traces");
+        let mut identified = Default::default();
+        trace.update_identified(0, &mapping, &mut identified, &evm);
+        assert!(trace.arena[trace.arena[0].children[0]].trace.delegate);
+
+        let (name, abi) = trace.resolve_proxy(0, &mapping, &mut identified, &evm).unwrap();
+        assert_eq!(name, "Proxy→ProxyImpl");
+        assert!(abi.function("setNum").is_ok());
+
+        // the implementation is resolved from the EIP-1967 slot as well
+        let mut arena = trace.clone();
+        arena.arena[0].children.clear();
+        let (name, _) = arena.resolve_proxy(0, &mapping, &mut identified, &evm).unwrap();
+        assert_eq!(name, "Proxy→ProxyImpl");
+
+        trace.pretty_print(0, &mapping, &Default::default(), &mut identified, &evm, "");
     }
 }
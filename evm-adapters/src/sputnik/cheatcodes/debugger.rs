@@ -57,6 +57,20 @@ impl DebugArena {
     }
 }
 
+/// Returns the breakpoints set with `vm.breakpoint` in a [flattened](DebugArena::flatten) arena,
+/// as their label and the index of the call and step they were hit at, in execution order
+pub fn breakpoints(flattened: &[(Address, Vec<DebugStep>, bool)]) -> Vec<(String, usize, usize)> {
+    flattened
+        .iter()
+        .enumerate()
+        .flat_map(|(call, (_, steps, _))| {
+            steps.iter().enumerate().filter_map(move |(step, debug_step)| {
+                debug_step.breakpoint.clone().map(|label| (label, call, step))
+            })
+        })
+        .collect()
+}
+
 #[derive(Default, Debug, Clone)]
 /// A node in the arena
 pub struct DebugNode {
@@ -103,6 +117,8 @@ pub struct DebugStep {
     pub ic: usize,
     /// Cumulative gas usage
     pub total_gas_used: u64,
+    /// The label of the breakpoint set with `vm.breakpoint` at this step, if any
+    pub breakpoint: Option<String>,
 }
 
 impl Default for DebugStep {
@@ -115,6 +131,7 @@ impl Default for DebugStep {
             push_bytes: None,
             ic: 0,
             total_gas_used: 0,
+            breakpoint: None,
         }
     }
 }
@@ -122,7 +139,9 @@ impl Default for DebugStep {
 impl DebugStep {
     /// Pretty print the step's opcode
     pub fn pretty_opcode(&self) -> String {
-        if let Some(push_bytes) = &self.push_bytes {
+        if let Some(label) = &self.breakpoint {
+            format!("{}({})", self.op, label)
+        } else if let Some(push_bytes) = &self.push_bytes {
             format!("{}(0x{})", self.op, hex::encode(push_bytes))
         } else {
             self.op.to_string()
@@ -179,6 +198,12 @@ pub enum CheatOp {
     CLEARMOCKEDCALLS,
     EXPECTCALL,
     GETCODE,
+    BREAKPOINT,
+    EXPECTMAXGAS,
+    READFILE,
+    PARSEJSON,
+    SERIALIZE,
+    WRITEJSON,
 }
 
 impl From<CheatOp> for OpCode {
@@ -212,6 +237,12 @@ impl CheatOp {
             CheatOp::CLEARMOCKEDCALLS => "VM_CLEARMOCKEDCALLS",
             CheatOp::EXPECTCALL => "VM_EXPECTCALL",
             CheatOp::GETCODE => "VM_GETCODE",
+            CheatOp::BREAKPOINT => "VM_BREAKPOINT",
+            CheatOp::EXPECTMAXGAS => "VM_EXPECTMAXGAS",
+            CheatOp::READFILE => "VM_READFILE",
+            CheatOp::PARSEJSON => "VM_PARSEJSON",
+            CheatOp::SERIALIZE => "VM_SERIALIZE",
+            CheatOp::WRITEJSON => "VM_WRITEJSON",
         }
     }
 }
@@ -0,0 +1,343 @@
+//! JSON reading and writing for the `parseJson`, `serialize*` and `writeJson` cheatcodes
+use ethers::{
+    abi::Token,
+    types::{Address, I256, U256},
+};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// The JSON objects built with the `serialize*` cheatcodes, by object key
+pub type SerializedObjects = BTreeMap<String, Map<String, Value>>;
+
+/// Prefixes the integers which do not fit in 64 bits while they are held as JSON strings, to tell
+/// them apart from strings which only contain digits
+const LARGE_INTEGER: &str = "\u{0}integer:";
+
+/// How [`LARGE_INTEGER`] is escaped at the start of a JSON string
+const QUOTED_LARGE_INTEGER: &str = "\"\\u0000integer:";
+
+/// Returns the ABI-encoded value at `key` in the JSON.
+///
+/// The key is a path of object keys and array indices separated by `.`, optionally starting with
+/// `$`, e.g. `.deployments.0.address`. `.` selects the whole JSON.
+pub fn parse_json(json: &str, key: &str) -> eyre::Result<Vec<u8>> {
+    let json: Value = serde_json::from_str(&quote_large_integers(json))?;
+    let path = key.strip_prefix('$').unwrap_or(key);
+    let mut value = &json;
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        value = match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(values) => segment.parse::<usize>().ok().and_then(|i| values.get(i)),
+            _ => None,
+        }
+        .ok_or_else(|| eyre::eyre!("key `{}` not found in the JSON", key))?;
+    }
+    Ok(ethers::abi::encode(&[value_to_token(value)?]))
+}
+
+/// Quotes the integers in the JSON which do not fit in 64 bits, which `serde_json` would otherwise
+/// round to floats, as strings prefixed with [`LARGE_INTEGER`]
+fn quote_large_integers(json: &str) -> String {
+    let mut quoted = String::with_capacity(json.len());
+    let mut chars = json.char_indices().peekable();
+    let mut in_string = false;
+    while let Some((start, c)) = chars.next() {
+        if in_string {
+            quoted.push(c);
+            match c {
+                '\\' => quoted.extend(chars.next().map(|(_, escaped)| escaped)),
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '-' || c.is_ascii_digit() {
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-')) {
+                    break
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let number = &json[start..end];
+            let is_integer = number.trim_start_matches('-').chars().all(|c| c.is_ascii_digit());
+            if is_integer && number.parse::<u64>().is_err() && number.parse::<i64>().is_err() {
+                quoted.push_str(QUOTED_LARGE_INTEGER);
+                quoted.push_str(number);
+                quoted.push('"');
+            } else {
+                quoted.push_str(number);
+            }
+        } else {
+            in_string = c == '"';
+            quoted.push(c);
+        }
+    }
+    quoted
+}
+
+/// Reverts [`quote_large_integers`] on the serialized JSON
+fn unquote_large_integers(json: &str) -> String {
+    let mut unquoted = String::with_capacity(json.len());
+    let mut rest = json;
+    while let Some(start) = rest.find(QUOTED_LARGE_INTEGER) {
+        unquoted.push_str(&rest[..start]);
+        rest = &rest[start + QUOTED_LARGE_INTEGER.len()..];
+        let end = rest.find('"').unwrap_or(rest.len());
+        unquoted.push_str(&rest[..end]);
+        rest = rest.get(end + 1..).unwrap_or_default();
+    }
+    unquoted.push_str(rest);
+    unquoted
+}
+
+/// Converts a JSON value to the token it is decoded as in Solidity.
+///
+/// Integers are `uint256`, or `int256` if negative. Hex strings are addresses if 20 bytes long,
+/// `bytes32` if 32 bytes long and `bytes` otherwise, other strings stay strings. Objects are tuples
+/// of their values, ordered by key.
+fn value_to_token(value: &Value) -> eyre::Result<Token> {
+    Ok(match value {
+        Value::Null => eyre::bail!("null can not be ABI encoded"),
+        Value::Bool(b) => Token::Bool(*b),
+        Value::Number(n) => {
+            if let Some(n) = n.as_u64() {
+                Token::Uint(n.into())
+            } else if let Some(n) = n.as_i64() {
+                Token::Int(I256::from(n).into_raw())
+            } else {
+                eyre::bail!("the fractional number {} can not be ABI encoded", n)
+            }
+        }
+        Value::String(s) => {
+            if let Some(integer) = s.strip_prefix(LARGE_INTEGER) {
+                return decimal_to_token(integer)
+                    .ok_or_else(|| eyre::eyre!("the integer {} does not fit in 256 bits", integer))
+            }
+            match s.strip_prefix("0x").map(hex::decode) {
+                Some(Ok(bytes)) if bytes.len() == 20 => Token::Address(Address::from_slice(&bytes)),
+                Some(Ok(bytes)) if bytes.len() == 32 => Token::FixedBytes(bytes),
+                Some(Ok(bytes)) => Token::Bytes(bytes),
+                _ => Token::String(s.clone()),
+            }
+        }
+        Value::Array(values) => {
+            Token::Array(values.iter().map(value_to_token).collect::<eyre::Result<_>>()?)
+        }
+        Value::Object(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(key, _)| *key);
+            Token::Tuple(
+                entries
+                    .into_iter()
+                    .map(|(_, value)| value_to_token(value))
+                    .collect::<eyre::Result<_>>()?,
+            )
+        }
+    })
+}
+
+/// Decodes a decimal integer as a `uint256`, or as an `int256` if negative. Returns `None` if it
+/// does not fit in 256 bits
+fn decimal_to_token(s: &str) -> Option<Token> {
+    if s.starts_with('-') {
+        I256::from_dec_str(s).ok().map(|n| Token::Int(n.into_raw()))
+    } else {
+        U256::from_dec_str(s).ok().map(Token::Uint)
+    }
+}
+
+/// Sets `value_key` of the object `object_key` to the value and returns the serialized object
+pub fn serialize(
+    objects: &mut SerializedObjects,
+    object_key: &str,
+    value_key: &str,
+    value: Value,
+) -> String {
+    let object = objects.entry(object_key.to_string()).or_default();
+    object.insert(value_key.to_string(), value);
+    unquote_large_integers(&Value::Object(object.clone()).to_string())
+}
+
+/// The JSON value of a `uint256`. `serde_json` numbers are limited to 64 bits, so larger values
+/// are held as strings until the JSON is serialized, see [`quote_large_integers`]
+pub fn uint_value(value: U256) -> Value {
+    if value <= U256::from(u64::MAX) {
+        Value::from(value.as_u64())
+    } else {
+        Value::String(format!("{}{}", LARGE_INTEGER, value))
+    }
+}
+
+/// The JSON value of a string. Strings which are JSON objects or arrays, e.g. the result of
+/// another `serialize*` call, are nested as such
+pub fn string_value(value: String) -> Value {
+    match serde_json::from_str(&quote_large_integers(&value)) {
+        Ok(nested @ (Value::Object(_) | Value::Array(_))) => nested,
+        _ => Value::String(value),
+    }
+}
+
+/// Writes the JSON to the file, pretty printed
+pub fn write_json(json: &str, path: &Path) -> eyre::Result<()> {
+    let json: Value = serde_json::from_str(&quote_large_integers(json))?;
+    std::fs::write(path, unquote_large_integers(&serde_json::to_string_pretty(&json)?))?;
+    Ok(())
+}
+
+/// Where the file cheatcodes may read and write
+#[derive(Clone, Debug, Default)]
+pub struct FileAccess {
+    /// The directory relative paths are resolved against, the working directory if empty
+    pub root: PathBuf,
+    /// The paths inside which files may be read and written, relative to the root
+    pub allowed_paths: Vec<PathBuf>,
+}
+
+impl FileAccess {
+    /// Resolves the path of a file accessed by a cheatcode relative to the root, failing unless
+    /// it is inside one of the allowed paths once all symlinks are resolved
+    pub fn resolve(&self, path: &str) -> eyre::Result<PathBuf> {
+        let path = self.root.join(path);
+        let resolved = match std::fs::canonicalize(&path) {
+            Ok(resolved) => resolved,
+            // writing through a dangling symlink would create its target
+            Err(_) if path.symlink_metadata().is_ok() => {
+                eyre::bail!("`{}` is a dangling symlink", path.display())
+            }
+            // the file may not exist yet when writing, so its directory is resolved instead
+            Err(_) => {
+                let file_name = path
+                    .file_name()
+                    .ok_or_else(|| eyre::eyre!("`{}` is not a file", path.display()))?;
+                let dir = path
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                std::fs::canonicalize(dir)?.join(file_name)
+            }
+        };
+        if self
+            .allowed_paths
+            .iter()
+            .filter_map(|allowed| std::fs::canonicalize(self.root.join(allowed)).ok())
+            .any(|allowed| resolved.starts_with(allowed))
+        {
+            Ok(resolved)
+        } else {
+            eyre::bail!(
+                "`{}` is not inside the paths allowed with --allow-path: {:?}",
+                path.display(),
+                self.allowed_paths
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_json_keys() {
+        let json = r#"{
+            "name": "Token",
+            "decimals": 18,
+            "owner": "0x1111111111111111111111111111111111111111",
+            "deployments": [{ "chain": 1, "salt": -1 }]
+        }"#;
+
+        let name = parse_json(json, ".name").unwrap();
+        assert_eq!(name, ethers::abi::encode(&[Token::String("Token".to_string())]));
+
+        let owner = parse_json(json, "$.owner").unwrap();
+        assert_eq!(owner, ethers::abi::encode(&[Token::Address(Address::from_slice(&[0x11; 20]))]));
+
+        let chain = parse_json(json, ".deployments.0.chain").unwrap();
+        assert_eq!(chain, ethers::abi::encode(&[Token::Uint(1u64.into())]));
+
+        // objects are tuples ordered by key
+        let deployment = parse_json(json, ".deployments.0").unwrap();
+        assert_eq!(
+            deployment,
+            ethers::abi::encode(&[Token::Tuple(vec![
+                Token::Uint(1u64.into()),
+                Token::Int(I256::from(-1i64).into_raw()),
+            ])])
+        );
+
+        // integers beyond 64 bits keep their precision, strings of digits stay strings
+        let large = format!(r#"{{"max": {}, "min": {}, "version": "2"}}"#, U256::MAX, I256::MIN);
+        let max = parse_json(&large, ".max").unwrap();
+        assert_eq!(max, ethers::abi::encode(&[Token::Uint(U256::MAX)]));
+        let min = parse_json(&large, ".min").unwrap();
+        assert_eq!(min, ethers::abi::encode(&[Token::Int(I256::MIN.into_raw())]));
+        let version = parse_json(&large, ".version").unwrap();
+        assert_eq!(version, ethers::abi::encode(&[Token::String("2".to_string())]));
+        assert!(parse_json(&format!(r#"{{"a": {}0}}"#, U256::MAX), ".a").is_err());
+
+        assert!(parse_json(json, ".missing").is_err());
+        assert!(parse_json(json, ".deployments.1").is_err());
+    }
+
+    #[test]
+    fn serializes_objects() {
+        let mut objects = SerializedObjects::default();
+        serialize(&mut objects, "token", "decimals", uint_value(18u64.into()));
+        let token = serialize(&mut objects, "token", "supply", uint_value(U256::MAX));
+        assert_eq!(token, format!(r#"{{"decimals":18,"supply":{}}}"#, U256::MAX));
+
+        let supply = parse_json(&token, ".supply").unwrap();
+        assert_eq!(supply, ethers::abi::encode(&[Token::Uint(U256::MAX)]));
+
+        let book = serialize(&mut objects, "book", "token", string_value(token));
+        assert_eq!(book, format!(r#"{{"token":{{"decimals":18,"supply":{}}}}}"#, U256::MAX));
+
+        let version = serialize(&mut objects, "release", "version", string_value("2".to_string()));
+        assert_eq!(version, r#"{"version":"2"}"#);
+    }
+
+    #[test]
+    fn writes_json_inside_allowed_paths() {
+        let root = std::env::temp_dir().join("forge-json-cheatcodes");
+        let dir = root.join("out");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert!(FileAccess { root: root.clone(), ..Default::default() }
+            .resolve("out/book.json")
+            .is_err());
+        // relative paths are resolved against the root
+        let access = FileAccess { root: root.clone(), allowed_paths: vec![PathBuf::from("out")] };
+        let path = access.resolve("out/book.json").unwrap();
+        assert_eq!(path, dir.canonicalize().unwrap().join("book.json"));
+
+        write_json(&format!(r#"{{"a":1,"max":{}}}"#, U256::MAX), &path).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, format!("{{\n  \"a\": 1,\n  \"max\": {}\n}}", U256::MAX));
+        assert!(write_json("not json", &path).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn rejects_symlinks_out_of_allowed_paths() {
+        let root = std::env::temp_dir().join("forge-json-cheatcodes-symlinks");
+        let dir = root.join("out");
+        std::fs::create_dir_all(&dir).unwrap();
+        let secret = root.join("secret.json");
+        std::fs::write(&secret, "{}").unwrap();
+        for (link, target) in
+            [("link.json", secret.clone()), ("dangling.json", root.join("missing.json"))]
+        {
+            let link = dir.join(link);
+            let _ = std::fs::remove_file(&link);
+            std::os::unix::fs::symlink(target, &link).unwrap();
+        }
+
+        let access = FileAccess { root, allowed_paths: vec![PathBuf::from("out")] };
+        assert!(access.resolve("out/link.json").is_err());
+        assert!(access.resolve("out/dangling.json").is_err());
+        assert!(access.resolve("out/new.json").is_ok());
+    }
+}
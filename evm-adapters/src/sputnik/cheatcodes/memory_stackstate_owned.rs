@@ -1,10 +1,14 @@
 use sputnik::{
-    backend::{Backend, Basic},
+    backend::{Apply, Backend, Basic},
     executor::stack::{MemoryStackSubstate, StackState, StackSubstateMetadata},
     ExitError, Transfer,
 };
 
-use crate::{call_tracing::CallTraceArena, sputnik::cheatcodes::debugger::DebugArena};
+use crate::{
+    call_tracing::CallTraceArena,
+    sputnik::cheatcodes::{debugger::DebugArena, json::SerializedObjects},
+    state_diff::{delta, AccountDiff, StateDiff},
+};
 
 use ethers::{
     abi::RawLog,
@@ -28,6 +32,26 @@ pub struct ExpectedEmit {
     pub found: bool,
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct ExpectedCall {
+    /// The calldata, or a prefix of it
+    pub data: Vec<u8>,
+    /// How often the call must be made, at least once if not set
+    pub count: Option<u64>,
+    /// How often the call was made
+    pub found: u64,
+}
+
+impl ExpectedCall {
+    /// Whether the expectation is met
+    pub fn is_satisfied(&self) -> bool {
+        match self.count {
+            Some(count) => self.found == count,
+            None => self.found > 0,
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct Prank {
     /// Address of the contract that called prank
@@ -70,7 +94,11 @@ pub struct MemoryStackStateOwned<'config, B> {
     /// Expected events by end of the next call
     pub expected_emits: Vec<ExpectedEmit>,
     pub mocked_calls: BTreeMap<H160, BTreeMap<Vec<u8>, Vec<u8>>>,
-    pub expected_calls: BTreeMap<H160, Vec<Vec<u8>>>,
+    pub expected_calls: BTreeMap<H160, Vec<ExpectedCall>>,
+    /// The maximum gas the current top-level call may use, set with `expectMaxGas`
+    pub gas_budget: Option<u64>,
+    /// The JSON objects built with the `serialize*` cheatcodes
+    pub serialized_jsons: SerializedObjects,
     /// Debug enabled
     pub debug_enabled: bool,
     /// An arena allocator of DebugNodes for debugging purposes
@@ -118,6 +146,45 @@ impl<'config, B: Backend> MemoryStackStateOwned<'config, B> {
 pub type Dip =
     (BTreeMap<H160, Rc<BTreeMap<usize, usize>>>, BTreeMap<H160, Rc<BTreeMap<usize, usize>>>);
 
+impl<'config, B: Backend> MemoryStackStateOwned<'config, B> {
+    /// Returns the changes made to the state since it was at `pre`
+    pub fn diff(&self, pre: &Self) -> StateDiff {
+        let (applies, _) = self.substate.clone().deconstruct(&self.backend);
+        let mut diff = StateDiff::default();
+        for apply in applies {
+            match apply {
+                Apply::Modify { address, basic, code, storage, .. } => {
+                    let before = pre.basic(address);
+                    let storage = storage
+                        .into_iter()
+                        .filter_map(|(slot, value)| {
+                            delta(pre.storage(address, slot), value).map(|delta| (slot, delta))
+                        })
+                        .collect();
+                    let created = code.map(|code| !code.is_empty()).unwrap_or_default() &&
+                        pre.code(address).is_empty();
+                    diff.insert(
+                        address,
+                        AccountDiff {
+                            balance: delta(before.balance, basic.balance),
+                            nonce: delta(before.nonce, basic.nonce),
+                            storage,
+                            created,
+                            ..Default::default()
+                        },
+                    );
+                }
+                Apply::Delete { address } => {
+                    if pre.exists(address) {
+                        diff.insert(address, AccountDiff { destroyed: true, ..Default::default() });
+                    }
+                }
+            }
+        }
+        diff
+    }
+}
+
 impl<'config, B: Backend> MemoryStackStateOwned<'config, B> {
     pub fn new(
         metadata: StackSubstateMetadata<'config>,
@@ -140,6 +207,8 @@ impl<'config, B: Backend> MemoryStackStateOwned<'config, B> {
             expected_emits: Default::default(),
             mocked_calls: Default::default(),
             expected_calls: Default::default(),
+            gas_budget: None,
+            serialized_jsons: Default::default(),
             debug_enabled,
             debug_steps: vec![Default::default()],
             debug_instruction_pointers: (BTreeMap::new(), BTreeMap::new()),
@@ -295,3 +364,45 @@ impl<'config, B: Backend> StackState<'config> for MemoryStackStateOwned<'config,
         self.substate.touch(address, &self.backend)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sputnik::helpers::new_vicinity;
+    use sputnik::{backend::MemoryBackend, Config};
+
+    #[test]
+    fn diffs_balance_nonce_and_storage() {
+        let config = Config::london();
+        let vicinity = new_vicinity();
+        let backend = MemoryBackend::new(&vicinity, Default::default());
+        let metadata = StackSubstateMetadata::new(u64::MAX, &config);
+        let mut state = MemoryStackStateOwned::new(metadata, backend, false, false);
+
+        let (account, untouched) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let slot = H256::from_low_u64_be(3);
+        state.set_storage(account, slot, H256::from_low_u64_be(4));
+        let pre = state.clone();
+
+        state.deposit(account, 100u64.into());
+        state.inc_nonce(account);
+        state.set_storage(account, slot, H256::from_low_u64_be(5));
+        state.set_storage(account, H256::from_low_u64_be(6), H256::from_low_u64_be(7));
+        // touched, but not changed
+        state.touch(untouched);
+
+        let diff = state.diff(&pre);
+        assert_eq!(diff.accounts.len(), 1);
+        let changes = &diff.accounts[&account];
+        assert_eq!(changes.balance, Some((0u64.into(), 100u64.into())));
+        assert_eq!(changes.nonce, Some((0u64.into(), 1u64.into())));
+        assert_eq!(
+            changes.storage,
+            BTreeMap::from([
+                (slot, (H256::from_low_u64_be(4), H256::from_low_u64_be(5))),
+                (H256::from_low_u64_be(6), (H256::zero(), H256::from_low_u64_be(7))),
+            ])
+        );
+        assert!(!changes.created && !changes.destroyed);
+    }
+}
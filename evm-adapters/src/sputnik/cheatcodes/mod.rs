@@ -11,7 +11,12 @@ pub mod backend;
 
 pub mod debugger;
 
-use ethers::types::{Address, Selector, H256, U256};
+pub mod json;
+
+use ethers::{
+    abi::Token,
+    types::{Address, Selector, H256, U256},
+};
 use once_cell::sync::Lazy;
 use sputnik::backend::{Backend, MemoryAccount, MemoryBackend};
 
@@ -70,7 +75,17 @@ ethers::contract::abigen!(
             mockCall(address,bytes,bytes)
             clearMockedCalls()
             expectCall(address,bytes)
+            expectCall(address,bytes,uint64)
             getCode(string)
+            breakpoint(string)
+            expectMaxGas(uint64)
+            readFile(string)(string)
+            parseJson(string,string)(bytes)
+            serializeUint(string,string,uint256)(string)
+            serializeAddress(string,string,address)(string)
+            serializeBool(string,string,bool)(string)
+            serializeString(string,string,string)(string)
+            writeJson(string,string)
     ]"#,
 );
 pub use hevm_mod::{HEVMCalls, HEVM_ABI};
@@ -99,6 +114,60 @@ ethers::contract::abigen!(
 
 // Bindings for hardhat console
 ethers::contract::abigen!(Console, "./testdata/console.json",);
+pub use console_mod::CONSOLE_ABI;
+
+/// Decodes a call to the `hardhat/console.log` address and formats it the way hardhat prints it.
+///
+/// If the first argument is a string, `%s`, `%d`, `%i` and `%o` in it are substituted with the
+/// following arguments and `%%` with `%`. Arguments left over are appended separated by a space,
+/// like node's `util.format` which hardhat uses.
+pub fn format_console_log(input: &[u8]) -> eyre::Result<String> {
+    let input = patch_hardhat_console_log_selector(input.to_vec());
+    if input.len() < 4 {
+        eyre::bail!("console call without selector: 0x{}", hex::encode(&input))
+    }
+    let function = CONSOLE_ABI
+        .functions()
+        .find(|func| func.short_signature() == input[..4])
+        .ok_or_else(|| eyre::eyre!("unknown console selector 0x{}", hex::encode(&input[..4])))?;
+    let tokens = function.decode_input(&input[4..])?;
+
+    let mut args = tokens.iter().map(|token| match token {
+        Token::String(s) => s.clone(),
+        token => foundry_utils::format_token(token),
+    });
+    let mut msg = String::new();
+    let mut separate = false;
+    if let Some(Token::String(fmt)) = tokens.first() {
+        separate = true;
+        args.next();
+        let mut chars = fmt.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek().copied()) {
+                ('%', Some('%')) => {
+                    chars.next();
+                    msg.push('%');
+                }
+                ('%', Some('s' | 'd' | 'i' | 'o')) => match args.next() {
+                    Some(arg) => {
+                        chars.next();
+                        msg.push_str(&arg);
+                    }
+                    None => msg.push(c),
+                },
+                _ => msg.push(c),
+            }
+        }
+    }
+    for arg in args {
+        if separate {
+            msg.push(' ');
+        }
+        msg.push_str(&arg);
+        separate = true;
+    }
+    Ok(msg)
+}
 
 /// If the input starts with a known `hardhat/console.log` `uint` selector, then this will replace
 /// it with the selector `abigen!` bindings expect.
@@ -578,4 +647,45 @@ mod tests {
             assert_eq!(abigen.to_vec(), patched);
         }
     }
+
+    fn console_call(sig: &str, args: &[Token]) -> Vec<u8> {
+        let mut input = ethers::utils::id(sig).to_vec();
+        input.extend(ethers::abi::encode(args));
+        input
+    }
+
+    #[test]
+    fn formats_console_logs() {
+        let input = console_call(
+            "log(string,string,uint)",
+            &[
+                Token::String("%s has %d tokens".into()),
+                Token::String("alice".into()),
+                Token::Uint(100.into()),
+            ],
+        );
+        assert_eq!(format_console_log(&input).unwrap(), "alice has 100 tokens");
+
+        let input = console_call(
+            "log(string,uint256)",
+            &[Token::String("100%% of %s".into()), Token::Uint(1.into())],
+        );
+        assert_eq!(format_console_log(&input).unwrap(), "100% of 1");
+
+        // no specifiers left, the arguments are appended
+        let input =
+            console_call("log(string,bool)", &[Token::String("%s".into()), Token::Bool(true)]);
+        assert_eq!(format_console_log(&input).unwrap(), "true");
+        let input =
+            console_call("log(string,bool)", &[Token::String("Hi".into()), Token::Bool(true)]);
+        assert_eq!(format_console_log(&input).unwrap(), "Hi true");
+        let input =
+            console_call("log(uint256,uint256)", &[Token::Uint(1.into()), Token::Uint(2.into())]);
+        assert_eq!(format_console_log(&input).unwrap(), "1 2");
+
+        let input = console_call("logBytes2(bytes2)", &[Token::FixedBytes(vec![0xbe, 0xef])]);
+        assert_eq!(format_console_log(&input).unwrap(), "0xbeef");
+
+        assert!(format_console_log(&[0xde, 0xad, 0xbe, 0xef]).is_err());
+    }
 }
@@ -1,5 +1,5 @@
 use crate::{call_tracing::CallTraceArena, Evm, FAUCET_ACCOUNT};
-use ethers::types::{Address, Bytes, U256};
+use ethers::types::{Address, Bytes, H256, U256};
 
 use crate::sputnik::cheatcodes::debugger::DebugArena;
 
@@ -123,6 +123,10 @@ where
         self.executor.state().code(address)
     }
 
+    fn storage(&self, address: Address, slot: H256) -> H256 {
+        self.executor.state().storage(address, slot)
+    }
+
     fn traces(&self) -> Vec<CallTraceArena> {
         self.executor.traces()
     }
@@ -241,6 +245,7 @@ pub mod helpers {
             &*CFG,
             &*PRECOMPILES_MAP,
             true,
+            Default::default(),
             false,
             false,
         )
@@ -256,6 +261,7 @@ pub mod helpers {
             &*CFG_NO_LMT,
             &*PRECOMPILES_MAP,
             true,
+            Default::default(),
             false,
             false,
         )
@@ -272,6 +278,7 @@ pub mod helpers {
                 &*CFG,
                 &*PRECOMPILES_MAP,
                 true,
+                Default::default(),
                 true,
                 false,
             )
@@ -282,6 +289,7 @@ pub mod helpers {
                 &*CFG_NO_LMT,
                 &*PRECOMPILES_MAP,
                 true,
+                Default::default(),
                 true,
                 false,
             )
@@ -299,6 +307,7 @@ pub mod helpers {
                 &*CFG,
                 &*PRECOMPILES_MAP,
                 true,
+                Default::default(),
                 false,
                 true,
             )
@@ -309,6 +318,7 @@ pub mod helpers {
                 &*CFG_NO_LMT,
                 &*PRECOMPILES_MAP,
                 true,
+                Default::default(),
                 false,
                 true,
             )
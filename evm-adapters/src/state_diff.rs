@@ -0,0 +1,105 @@
+//! Changes made to the state by an execution
+use ethers::{
+    abi::Abi,
+    types::{Address, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::call_tracing::diff_score;
+
+/// All accounts whose state changed during an execution
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateDiff {
+    pub accounts: BTreeMap<Address, AccountDiff>,
+}
+
+/// The changes made to a single account, as `(before, after)` pairs
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountDiff {
+    /// The name of the contract deployed at the account, if it is a known contract
+    pub contract: Option<String>,
+    pub balance: Option<(U256, U256)>,
+    pub nonce: Option<(U256, U256)>,
+    pub storage: BTreeMap<H256, (H256, H256)>,
+    /// Whether the account had no code before
+    pub created: bool,
+    /// Whether the account self-destructed
+    pub destroyed: bool,
+}
+
+impl AccountDiff {
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none() &&
+            self.nonce.is_none() &&
+            self.storage.is_empty() &&
+            !self.created &&
+            !self.destroyed
+    }
+}
+
+impl StateDiff {
+    /// Inserts the changes to an account, skipping accounts which were touched without changes
+    pub fn insert(&mut self, address: Address, account: AccountDiff) {
+        if !account.is_empty() {
+            self.accounts.insert(address, account);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Names the accounts whose code matches one of the known contracts
+    pub fn identify(
+        &mut self,
+        known_contracts: &BTreeMap<String, (Abi, Vec<u8>)>,
+        code: impl Fn(Address) -> Vec<u8>,
+    ) {
+        for (address, account) in self.accounts.iter_mut() {
+            let code = code(*address);
+            if code.is_empty() {
+                continue
+            }
+            account.contract = known_contracts
+                .iter()
+                .find(|(_, (_, known_code))| diff_score(known_code, &code) < 0.10)
+                .map(|(name, _)| name.clone());
+        }
+    }
+}
+
+/// Returns `Some((before, after))` if the value changed
+pub fn delta<T: PartialEq>(before: T, after: T) -> Option<(T, T)> {
+    if before == after {
+        None
+    } else {
+        Some((before, after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_unchanged_accounts() {
+        let mut diff = StateDiff::default();
+        diff.insert(
+            Address::from_low_u64_be(1),
+            AccountDiff { balance: delta(1u64.into(), 1u64.into()), ..Default::default() },
+        );
+        assert!(diff.is_empty());
+
+        diff.insert(
+            Address::from_low_u64_be(2),
+            AccountDiff { nonce: delta(0u64.into(), 1u64.into()), ..Default::default() },
+        );
+        assert_eq!(diff.accounts.len(), 1);
+        assert_eq!(
+            diff.accounts[&Address::from_low_u64_be(2)].nonce,
+            Some((U256::zero(), U256::one()))
+        );
+    }
+}
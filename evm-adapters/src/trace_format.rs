@@ -0,0 +1,207 @@
+//! Exports call traces in the formats of other tools
+use crate::call_tracing::CallTraceArena;
+use ethers::types::{Address, H160, U256};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// The format to export call traces in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// A flat list of traces, as returned by Parity's / OpenEthereum's `trace_transaction`
+    Parity,
+    /// A tree of calls, as returned by geth's `debug_traceTransaction` with the `callTracer`
+    Geth,
+    /// The call trace arena, serialized as is
+    Json,
+}
+
+impl FromStr for TraceFormat {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "parity" => TraceFormat::Parity,
+            "geth" => TraceFormat::Geth,
+            "json" => TraceFormat::Json,
+            other => eyre::bail!("unknown trace format {}, expected parity, geth or json", other),
+        })
+    }
+}
+
+impl CallTraceArena {
+    /// Exports the trace in the given format. `from` is the sender of the traced call.
+    ///
+    /// The gas given to each call is not recorded, so the gas used is reported in its place.
+    pub fn export(&self, format: TraceFormat, from: Address) -> Value {
+        match format {
+            TraceFormat::Parity => {
+                let mut traces = Vec::new();
+                self.parity_traces(self.entry, from, Vec::new(), &mut traces);
+                Value::Array(traces)
+            }
+            TraceFormat::Geth => self.geth_trace(self.entry, from),
+            TraceFormat::Json => serde_json::to_value(self).unwrap_or_default(),
+        }
+    }
+
+    /// The address whose storage and balance a call operates on, which is the caller's for
+    /// delegatecalls
+    fn context(&self, idx: usize) -> H160 {
+        let node = &self.arena[idx];
+        match node.parent {
+            Some(parent) if node.trace.delegate => self.context(parent),
+            _ => node.trace.addr,
+        }
+    }
+
+    fn parity_traces(
+        &self,
+        idx: usize,
+        from: Address,
+        trace_address: Vec<usize>,
+        traces: &mut Vec<Value>,
+    ) {
+        let node = &self.arena[idx];
+        let trace = &node.trace;
+        let (kind, mut action, result) = if trace.created {
+            (
+                "create",
+                json!({ "from": from, "value": trace.value, "init": hex_data(&trace.data) }),
+                json!({
+                    "address": trace.addr,
+                    "code": hex_data(&trace.output),
+                    "gasUsed": U256::from(trace.cost),
+                }),
+            )
+        } else {
+            let call_type = if trace.delegate { "delegatecall" } else { "call" };
+            (
+                "call",
+                json!({
+                    "callType": call_type,
+                    "from": from,
+                    "to": trace.addr,
+                    "value": trace.value,
+                    "input": hex_data(&trace.data),
+                }),
+                json!({ "gasUsed": U256::from(trace.cost), "output": hex_data(&trace.output) }),
+            )
+        };
+        action["gas"] = json!(U256::from(trace.cost));
+
+        let mut entry = json!({
+            "action": action,
+            "subtraces": node.children.len(),
+            "traceAddress": trace_address,
+            "type": kind,
+        });
+        if trace.success {
+            entry["result"] = result;
+        } else {
+            entry["error"] = json!("Reverted");
+        }
+        traces.push(entry);
+
+        let context = self.context(idx);
+        for (i, child) in node.children.iter().enumerate() {
+            let mut child_address = trace_address.clone();
+            child_address.push(i);
+            self.parity_traces(*child, context, child_address, traces);
+        }
+    }
+
+    fn geth_trace(&self, idx: usize, from: Address) -> Value {
+        let node = &self.arena[idx];
+        let trace = &node.trace;
+        let kind = if trace.created {
+            "CREATE"
+        } else if trace.delegate {
+            "DELEGATECALL"
+        } else {
+            "CALL"
+        };
+        let mut call = json!({
+            "type": kind,
+            "from": from,
+            "to": trace.addr,
+            "value": trace.value,
+            "gas": U256::from(trace.cost),
+            "gasUsed": U256::from(trace.cost),
+            "input": hex_data(&trace.data),
+            "output": hex_data(&trace.output),
+        });
+        if !trace.success {
+            call["error"] = json!("execution reverted");
+        }
+
+        let context = self.context(idx);
+        let calls =
+            node.children.iter().map(|child| self.geth_trace(*child, context)).collect::<Vec<_>>();
+        if !calls.is_empty() {
+            call["calls"] = Value::Array(calls);
+        }
+        call
+    }
+}
+
+fn hex_data(data: &[u8]) -> String {
+    format!("0x{}", hex::encode(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call_tracing::CallTrace;
+
+    fn arena() -> CallTraceArena {
+        let mut arena = CallTraceArena::default();
+        arena.arena[0].trace = CallTrace {
+            success: true,
+            addr: Address::from_low_u64_be(1),
+            data: vec![0xaa],
+            cost: 100,
+            ..Default::default()
+        };
+        let mut delegate = CallTrace {
+            depth: 1,
+            success: false,
+            addr: Address::from_low_u64_be(2),
+            delegate: true,
+            cost: 10,
+            ..Default::default()
+        };
+        arena.push_trace(0, &mut delegate);
+        let mut call = CallTrace {
+            depth: 2,
+            success: true,
+            addr: Address::from_low_u64_be(3),
+            ..Default::default()
+        };
+        arena.push_trace(0, &mut call);
+        arena
+    }
+
+    #[test]
+    fn exports_parity_traces() {
+        let traces = arena().export(TraceFormat::Parity, Address::zero());
+        let traces = traces.as_array().unwrap();
+        assert_eq!(traces.len(), 3);
+        assert_eq!(traces[0]["subtraces"], 1);
+        assert_eq!(traces[0]["action"]["input"], "0xaa");
+        assert_eq!(traces[1]["action"]["callType"], "delegatecall");
+        assert_eq!(traces[1]["error"], "Reverted");
+        assert_eq!(traces[2]["traceAddress"], json!([0, 0]));
+        // the delegatecall runs in the context of its caller
+        assert_eq!(traces[2]["action"]["from"], json!(Address::from_low_u64_be(1)));
+    }
+
+    #[test]
+    fn exports_geth_traces() {
+        let trace = arena().export(TraceFormat::Geth, Address::zero());
+        assert_eq!(trace["type"], "CALL");
+        assert_eq!(trace["calls"][0]["type"], "DELEGATECALL");
+        assert_eq!(trace["calls"][0]["error"], "execution reverted");
+        assert_eq!(trace["calls"][0]["calls"][0]["from"], json!(Address::from_low_u64_be(1)));
+        assert!(trace["calls"][0]["calls"][0].get("calls").is_none());
+    }
+}
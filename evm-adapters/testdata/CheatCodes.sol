@@ -56,8 +56,25 @@ interface Hevm {
     // Expect a call to an address with the specified calldata.
     // Calldata can either be strict or a partial match
     function expectCall(address,bytes calldata) external;
+    // Expect a call to an address with the specified calldata to be made exactly `count` times
+    function expectCall(address,bytes calldata,uint64) external;
 
     function getCode(string calldata) external returns (bytes memory);
+    // Labels the current point of execution, so that the debugger can jump to it
+    function breakpoint(string calldata) external;
+    // Fails the current call if it uses more gas than the budget
+    function expectMaxGas(uint64) external;
+    // Reads a file inside the paths allowed with `--allow-path`, (path) => (contents)
+    function readFile(string calldata) external returns (string memory);
+    // Gets the ABI-encoded value at a key of a JSON, (json, key) => (abi encoded value)
+    function parseJson(string calldata, string calldata) external returns (bytes memory);
+    // Sets a value of a JSON object, (objectKey, valueKey, value) => (serialized object)
+    function serializeUint(string calldata, string calldata, uint256) external returns (string memory);
+    function serializeAddress(string calldata, string calldata, address) external returns (string memory);
+    function serializeBool(string calldata, string calldata, bool) external returns (string memory);
+    function serializeString(string calldata, string calldata, string calldata) external returns (string memory);
+    // Writes a JSON to a file inside the paths allowed with `--allow-path`, (json, path)
+    function writeJson(string calldata, string calldata) external;
 }
 
 contract HasStorage {
@@ -560,6 +577,39 @@ contract CheatCodes is DSTest {
         );
     }
 
+    function testExpectCallCount() public {
+        MockMe target = new MockMe();
+        hevm.expectCall(
+            address(target),
+            abi.encodeWithSelector(target.add.selector, 1, 2),
+            2
+        );
+        target.add(1, 2);
+        target.add(1, 2);
+        target.add(2, 2);
+    }
+
+    function testExpectNoCall() public {
+        MockMe target = new MockMe();
+        hevm.expectCall(
+            address(target),
+            abi.encodeWithSelector(target.add.selector),
+            0
+        );
+        target.numberA();
+    }
+
+    function testFailExpectCallCount() public {
+        MockMe target = new MockMe();
+        hevm.expectCall(
+            address(target),
+            abi.encodeWithSelector(target.add.selector),
+            1
+        );
+        target.add(1, 2);
+        target.add(1, 2);
+    }
+
     function testGetCode() public {
         bytes memory contractCode = hevm.getCode("./testdata/Contract.json");
         assertEq(
@@ -568,6 +618,61 @@ contract CheatCodes is DSTest {
         );
     }
 
+    function testBreakpoint() public {
+        hevm.breakpoint("a");
+        uint256 x = 1;
+        hevm.breakpoint("b");
+        assertEq(x, 1);
+    }
+
+    function testExpectMaxGas() public {
+        hevm.expectMaxGas(100000);
+        uint256 x = 1;
+        assertEq(x, 1);
+    }
+
+    function testFailExpectMaxGas() public {
+        hevm.expectMaxGas(1000);
+        for (uint256 i = 0; i < 100; i++) {
+            hevm.roll(i);
+        }
+    }
+
+    function testParseJson() public {
+        string memory json = hevm.readFile("./testdata/fixture.json");
+        assertEq(abi.decode(hevm.parseJson(json, ".name"), (string)), "Token");
+        assertEq(abi.decode(hevm.parseJson(json, ".decimals"), (uint256)), 18);
+        assertEq(
+            abi.decode(hevm.parseJson(json, ".owner"), (address)),
+            address(0x1111111111111111111111111111111111111111)
+        );
+        address[] memory holders = abi.decode(hevm.parseJson(json, ".holders"), (address[]));
+        assertEq(holders.length, 2);
+        assertEq(holders[1], address(0x3333333333333333333333333333333333333333));
+    }
+
+    function testFailReadFileOutsideAllowedPaths() public {
+        hevm.readFile("./Cargo.toml");
+    }
+
+    function testSerializeJson() public {
+        hevm.serializeUint("token", "decimals", 18);
+        string memory token = hevm.serializeAddress(
+            "token",
+            "owner",
+            address(0x1111111111111111111111111111111111111111)
+        );
+        assertEq(token, '{"decimals":18,"owner":"0x1111111111111111111111111111111111111111"}');
+
+        string memory book = hevm.serializeString("book", "token", token);
+        assertEq(abi.decode(hevm.parseJson(book, ".token.decimals"), (uint256)), 18);
+    }
+
+    function testSerializeJsonMaxUint() public {
+        string memory json = hevm.serializeUint("max", "value", type(uint256).max);
+        assertEq(abi.decode(hevm.parseJson(json, ".value"), (uint256)), type(uint256).max);
+    }
+
     function getCode(address who) internal returns (bytes memory o_code) {
         assembly {
             // retrieve the size of the code, this needs assembly
@@ -19,4 +19,11 @@ contract ConsoleLogs {
 		console.logUint(1245);
 		console.logBool(true);
 	}
+
+	function test_log_format() public {
+		console.log("%s has %d tokens", "alice", 100);
+		console.log("%s sent %s", 0x1111111111111111111111111111111111111111, true);
+		console.log("100%% done");
+		console.logBytes2(0xbeef);
+	}
 }
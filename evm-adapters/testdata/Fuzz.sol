@@ -4,4 +4,8 @@ contract FuzzTests {
     function testFuzzedRevert(uint256 x) public {
         require(x == 5, "fuzztest-revert");
     }
+
+    function testFuzzedPass(uint256 x) public {
+        require(x == x);
+    }
 }
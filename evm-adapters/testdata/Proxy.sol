@@ -0,0 +1,32 @@
+pragma solidity ^0.8.0;
+
+contract ProxyImpl {
+	uint256 public num;
+
+	function setNum(uint256 _num) public {
+		num = _num;
+	}
+}
+
+// Minimal EIP-1967 proxy
+contract Proxy {
+	bytes32 internal constant IMPLEMENTATION_SLOT = 0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc;
+
+	constructor(address implementation) {
+		assembly {
+			sstore(IMPLEMENTATION_SLOT, implementation)
+		}
+	}
+
+	fallback() external payable {
+		assembly {
+			let implementation := sload(IMPLEMENTATION_SLOT)
+			calldatacopy(0, 0, calldatasize())
+			let result := delegatecall(gas(), implementation, 0, calldatasize(), 0, 0)
+			returndatacopy(0, 0, returndatasize())
+			switch result
+			case 0 { revert(0, returndatasize()) }
+			default { return(0, returndatasize()) }
+		}
+	}
+}
@@ -0,0 +1,235 @@
+//! Comparison of locally compiled bytecode against the bytecode deployed on chain
+
+use std::fmt;
+
+/// `PUSH20`, the first instruction of library runtime code which pushes the library's address
+const PUSH20: u8 = 0x73;
+/// `PUSH32`, used by solc to load immutables, which are zero in the compiled runtime code
+const PUSH32: u8 = 0x7f;
+
+/// The start of the CBOR encoded metadata solc appends, followed by the metadata hash
+const METADATA_HASH_PREFIXES: &[(&[u8], usize)] = &[
+    // {"ipfs": bytes(34)
+    (&[0x64, b'i', b'p', b'f', b's', 0x58, 0x22], 34),
+    // {"bzzr0": bytes(32)
+    (&[0x65, b'b', b'z', b'z', b'r', b'0', 0x58, 0x20], 32),
+    // {"bzzr1": bytes(32)
+    (&[0x65, b'b', b'z', b'z', b'r', b'1', 0x58, 0x20], 32),
+];
+
+/// How closely the local bytecode matches the one on chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BytecodeMatch {
+    /// The bytecode is identical, including the metadata hash
+    Exact,
+    /// The bytecode only differs in the parts which do not change the executed code
+    Partial(Differences),
+    /// The bytecode differs
+    Mismatch,
+}
+
+/// The parts in which partially matching bytecode differs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Differences {
+    /// The metadata hash differs, e.g. because of different comments or file paths
+    pub metadata: bool,
+    /// Immutables were set in the constructor
+    pub immutables: bool,
+    /// The code is a library's, which embeds the address it was deployed at
+    pub library_address: bool,
+}
+
+impl fmt::Display for Differences {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = [
+            (self.metadata, "metadata"),
+            (self.immutables, "immutables"),
+            (self.library_address, "library address"),
+        ]
+        .iter()
+        .filter(|(differs, _)| *differs)
+        .map(|(_, part)| *part)
+        .collect::<Vec<_>>();
+        f.write_str(&parts.join(", "))
+    }
+}
+
+impl BytecodeMatch {
+    /// Whether the executed code is the same
+    pub fn is_match(&self) -> bool {
+        !matches!(self, BytecodeMatch::Mismatch)
+    }
+}
+
+impl fmt::Display for BytecodeMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BytecodeMatch::Exact => f.write_str("exact match"),
+            BytecodeMatch::Partial(differences) => {
+                write!(f, "partial match (differs in {})", differences)
+            }
+            BytecodeMatch::Mismatch => f.write_str("mismatch"),
+        }
+    }
+}
+
+/// Returns the code with all metadata hashes zeroed, including the ones of nested contracts
+/// embedded in creation code
+pub fn mask_metadata(code: &[u8]) -> Vec<u8> {
+    let mut masked = code.to_vec();
+    for (prefix, hash_len) in METADATA_HASH_PREFIXES {
+        let mut i = 0;
+        while i + prefix.len() + hash_len <= masked.len() {
+            if masked[i..].starts_with(prefix) {
+                let start = i + prefix.len();
+                masked[start..start + hash_len].iter_mut().for_each(|b| *b = 0);
+                i = start + hash_len;
+            } else {
+                i += 1;
+            }
+        }
+    }
+    masked
+}
+
+/// Compares compiled runtime code against the code deployed on chain.
+///
+/// Immutables and the address of libraries are only known at deployment, so they are taken
+/// from the deployed code when comparing partially.
+pub fn compare_runtime(local: &[u8], onchain: &[u8]) -> BytecodeMatch {
+    if local == onchain {
+        return BytecodeMatch::Exact
+    }
+    if local.len() != onchain.len() {
+        return BytecodeMatch::Mismatch
+    }
+
+    // placeholders are looked up in the masked code so that metadata is not mistaken for code
+    let masked = mask_metadata(local);
+    let mut local = local.to_vec();
+    let mut differences = Differences::default();
+    let is_zero = |bytes: &[u8]| bytes.iter().all(|b| *b == 0);
+
+    // library address placeholder
+    if local.len() > 21 && masked[0] == PUSH20 && is_zero(&masked[1..21]) {
+        local[1..21].copy_from_slice(&onchain[1..21]);
+        differences.library_address = !is_zero(&onchain[1..21]);
+    }
+
+    // immutables, which are pushed with a zero placeholder
+    let mut pc = 0;
+    while pc < masked.len() {
+        let op = masked[pc];
+        if op == PUSH32 && pc + 33 <= masked.len() && is_zero(&masked[pc + 1..pc + 33]) {
+            local[pc + 1..pc + 33].copy_from_slice(&onchain[pc + 1..pc + 33]);
+            differences.immutables |= !is_zero(&onchain[pc + 1..pc + 33]);
+        }
+        pc += 1 + push_size(op);
+    }
+
+    if local != onchain {
+        if mask_metadata(&local) != mask_metadata(onchain) {
+            return BytecodeMatch::Mismatch
+        }
+        differences.metadata = true;
+    }
+    BytecodeMatch::Partial(differences)
+}
+
+/// Compares compiled creation code against the input of the contract creation transaction,
+/// returning the match and the constructor arguments appended to the creation code
+pub fn compare_creation<'a>(local: &[u8], input: &'a [u8]) -> (BytecodeMatch, &'a [u8]) {
+    if input.len() < local.len() {
+        return (BytecodeMatch::Mismatch, &[])
+    }
+    let (code, args) = input.split_at(local.len());
+    let res = if code == local {
+        BytecodeMatch::Exact
+    } else if mask_metadata(code) == mask_metadata(local) {
+        BytecodeMatch::Partial(Differences { metadata: true, ..Default::default() })
+    } else {
+        BytecodeMatch::Mismatch
+    };
+    (res, args)
+}
+
+/// Number of immediate bytes of the opcode
+fn push_size(op: u8) -> usize {
+    if (0x60..=0x7f).contains(&op) {
+        (op - 0x5f) as usize
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_metadata(code: &[u8], hash: u8) -> Vec<u8> {
+        let mut code = code.to_vec();
+        code.extend_from_slice(&[0xa2, 0x64, b'i', b'p', b'f', b's', 0x58, 0x22]);
+        code.extend_from_slice(&[hash; 34]);
+        code.extend_from_slice(&[0x64, b's', b'o', b'l', b'c', 0x43, 0, 8, 10, 0, 0x33]);
+        code
+    }
+
+    #[test]
+    fn compares_runtime_code() {
+        let local = with_metadata(&[0x60, 0x80, 0x60, 0x40, 0x52], 1);
+        assert_eq!(compare_runtime(&local, &local), BytecodeMatch::Exact);
+        let other_metadata = with_metadata(&[0x60, 0x80, 0x60, 0x40, 0x52], 2);
+        let res = compare_runtime(&local, &other_metadata);
+        assert_eq!(
+            res,
+            BytecodeMatch::Partial(Differences { metadata: true, ..Default::default() })
+        );
+        assert_eq!(res.to_string(), "partial match (differs in metadata)");
+        let other_code = with_metadata(&[0x60, 0x80, 0x60, 0x41, 0x52], 1);
+        assert_eq!(compare_runtime(&local, &other_code), BytecodeMatch::Mismatch);
+    }
+
+    #[test]
+    fn fills_in_immutables() {
+        let mut local = vec![PUSH32];
+        local.extend_from_slice(&[0; 32]);
+        local.push(0x50);
+        let mut onchain = vec![PUSH32];
+        onchain.extend_from_slice(&[7; 32]);
+        onchain.push(0x50);
+        let res = compare_runtime(&local, &onchain);
+        assert_eq!(
+            res,
+            BytecodeMatch::Partial(Differences { immutables: true, ..Default::default() })
+        );
+        assert_eq!(res.to_string(), "partial match (differs in immutables)");
+
+        // along with the metadata
+        assert_eq!(
+            compare_runtime(&with_metadata(&local, 1), &with_metadata(&onchain, 2)).to_string(),
+            "partial match (differs in metadata, immutables)"
+        );
+
+        // only placeholders may differ
+        local[1] = 1;
+        assert_eq!(compare_runtime(&local, &onchain), BytecodeMatch::Mismatch);
+    }
+
+    #[test]
+    fn splits_constructor_args() {
+        let local = with_metadata(&[0x60, 0x80], 1);
+        let mut input = with_metadata(&[0x60, 0x80], 3);
+        input.extend_from_slice(&[0; 31]);
+        input.push(42);
+
+        let (res, args) = compare_creation(&local, &input);
+        assert_eq!(
+            res,
+            BytecodeMatch::Partial(Differences { metadata: true, ..Default::default() })
+        );
+        assert_eq!(args.len(), 32);
+        assert_eq!(args[31], 42);
+
+        assert_eq!(compare_creation(&local, &[0x60]).0, BytecodeMatch::Mismatch);
+    }
+}
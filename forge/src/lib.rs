@@ -4,6 +4,14 @@ pub use runner::{ContractRunner, TestKind, TestKindGas, TestResult};
 mod multi_runner;
 pub use multi_runner::{MultiContractRunner, MultiContractRunnerBuilder};
 
+mod bytecode;
+pub use bytecode::{compare_creation, compare_runtime, mask_metadata, BytecodeMatch, Differences};
+
+mod storage_layout;
+pub use storage_layout::{
+    LayoutChange, StorageLayout, StoragePosition, StorageType, StorageVariable,
+};
+
 pub trait TestFilter {
     fn matches_test(&self, test_name: &str) -> bool;
     fn matches_contract(&self, contract_name: &str) -> bool;
@@ -1,8 +1,15 @@
-use crate::{runner::TestResult, ContractRunner, TestFilter};
-use evm_adapters::evm_opts::{BackendKind, EvmOpts};
+use crate::{runner::TestResult, ContractRunner, StorageLayout, TestFilter};
+use evm_adapters::{
+    evm_opts::{BackendKind, EvmOpts, EvmType},
+    external::ExternalEvm,
+    signatures::SignatureCache,
+};
 use sputnik::{backend::Backend, Config};
 
-use ethers::solc::Artifact;
+use ethers::solc::{
+    artifacts::{CompactContract, CompilerOutput},
+    Artifact, ProjectCompileOutput,
+};
 
 use ethers::{
     abi::Abi,
@@ -15,7 +22,17 @@ use proptest::test_runner::TestRunner;
 
 use eyre::Result;
 use rayon::prelude::*;
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::Mutex, time::Instant};
+
+/// The file next to the solc cache file where the selector index is persisted
+const SIGNATURES_CACHE_FILE: &str = "signatures.json";
+
+/// The file next to the solc cache file where the storage layouts are persisted
+const STORAGE_LAYOUTS_CACHE_FILE: &str = "storage-layouts.json";
+
+/// A contract's name along with its abi, creation and runtime bytecode
+type ContractParts =
+    (String, (Option<Abi>, Option<ethers::prelude::Bytes>, Option<ethers::prelude::Bytes>));
 
 /// Builder used for instantiating the multi-contract runner
 #[derive(Debug, Default)]
@@ -34,31 +51,36 @@ pub struct MultiContractRunnerBuilder {
 impl MultiContractRunnerBuilder {
     /// Given an EVM, proceeds to return a runner which is able to execute all tests
     /// against that evm
-    pub fn build<A>(self, project: Project<A>, evm_opts: EvmOpts) -> Result<MultiContractRunner>
+    pub fn build<A>(
+        self,
+        mut project: Project<A>,
+        mut evm_opts: EvmOpts,
+    ) -> Result<MultiContractRunner>
     where
         // TODO: Can we remove the static? It's due to the `into_artifacts()` call below
         A: ArtifactOutput + 'static,
     {
-        println!("compiling...");
-        let output = project.compile()?;
-        if output.has_compiler_errors() {
-            // return the diagnostics error back to the user.
-            eyre::bail!(output.to_string())
-        } else if output.is_unchanged() {
-            println!("no files changed, compilation skipped.");
-        } else {
-            println!("success.");
+        // storage layouts are needed to name the storage slots of state diffs
+        if evm_opts.state_diff {
+            StorageLayout::request(&mut project);
         }
 
-        // This is just the contracts compiled, but we need to merge this with the read cached
-        // artifacts
-        let contracts = output.into_artifacts();
+        let output = compile(&project)?;
+        evm_opts.root = project.paths.root.clone();
+
+        let mut storage_layouts = BTreeMap::new();
+        let contracts = if evm_opts.state_diff {
+            contracts_with_layouts(&mut project, output, &mut storage_layouts)?
+        } else {
+            // This is just the contracts compiled, but we need to merge this with the read cached
+            // artifacts
+            artifacts(output)
+        };
         let mut known_contracts: BTreeMap<String, (Abi, Vec<u8>)> = Default::default();
         let mut deployable_contracts: BTreeMap<String, (Abi, ethers::prelude::Bytes)> =
             Default::default();
 
-        for (fname, contract) in contracts {
-            let (maybe_abi, maybe_deploy_bytes, maybe_runtime_bytes) = contract.into_parts();
+        for (fname, (maybe_abi, maybe_deploy_bytes, maybe_runtime_bytes)) in contracts {
             if let (Some(abi), Some(bytecode)) = (maybe_abi, maybe_deploy_bytes) {
                 // skip deployment of abstract contracts
                 if bytecode.as_ref().is_empty() {
@@ -79,14 +101,42 @@ impl MultiContractRunnerBuilder {
             }
         }
 
+        // let external execution environments replace the solc output with their own bytecode,
+        // the process is then reused to run the tests
+        let external = match evm_opts.evm_type {
+            EvmType::External(ref cmd) => {
+                let evm = ExternalEvm::spawn(cmd)?;
+                evm.compile(&project.paths.root, &mut deployable_contracts)?;
+                Some(Mutex::new(evm))
+            }
+            _ => None,
+        };
+
+        // index all selectors and events for trace decoding, reusing the ones from previous runs
+        let signatures_path = project.paths.cache.with_file_name(SIGNATURES_CACHE_FILE);
+        let mut signatures = if project.cached {
+            SignatureCache::load(&signatures_path)
+        } else {
+            SignatureCache::default()
+        };
+        known_contracts.values().for_each(|(abi, _)| signatures.extend_from_abi(abi));
+        if project.cached {
+            if let Err(err) = signatures.save(&signatures_path) {
+                tracing::warn!(?err, "failed to write signature cache");
+            }
+        }
+
         Ok(MultiContractRunner {
             contracts: deployable_contracts,
             known_contracts,
+            storage_layouts,
+            signatures,
             identified_contracts: Default::default(),
             evm_opts,
             evm_cfg: self.evm_cfg.unwrap_or_else(Config::london),
             sender: self.sender,
             fuzzer: self.fuzzer,
+            external,
         })
     }
 
@@ -115,6 +165,92 @@ impl MultiContractRunnerBuilder {
     }
 }
 
+/// Compiles the project, returning the diagnostics on compiler errors
+fn compile<A: ArtifactOutput>(project: &Project<A>) -> Result<ProjectCompileOutput<A>> {
+    println!("compiling...");
+    let output = project.compile()?;
+    if output.has_compiler_errors() {
+        // return the diagnostics error back to the user.
+        eyre::bail!(output.to_string())
+    } else if output.is_unchanged() {
+        println!("no files changed, compilation skipped.");
+    } else {
+        println!("success.");
+    }
+    Ok(output)
+}
+
+/// All artifacts of the project, the freshly compiled and the cached ones
+fn artifacts<A: ArtifactOutput + 'static>(output: ProjectCompileOutput<A>) -> Vec<ContractParts> {
+    output.into_artifacts().map(|(name, artifact)| (name, artifact.into_parts())).collect()
+}
+
+/// Collects the storage layouts of all contracts in the compiler output
+fn extend_layouts(output: &CompilerOutput, storage_layouts: &mut BTreeMap<String, StorageLayout>) {
+    for (name, contract) in output.contracts.values().flatten() {
+        if let Some(layout) = StorageLayout::from_contract(contract) {
+            storage_layouts.insert(name.clone(), layout);
+        }
+    }
+}
+
+/// Returns the contracts of the project and collects their storage layouts.
+///
+/// Storage layouts are not part of the artifacts, so for cached projects they are persisted next
+/// to the solc cache. Everything is only recompiled if some cached artifacts have no known layout,
+/// e.g. because they were compiled by a run without `--state-diff`.
+fn contracts_with_layouts<A: ArtifactOutput + 'static>(
+    project: &mut Project<A>,
+    output: ProjectCompileOutput<A>,
+    storage_layouts: &mut BTreeMap<String, StorageLayout>,
+) -> Result<Vec<ContractParts>> {
+    if !project.cached {
+        let output = output.output();
+        extend_layouts(&output, storage_layouts);
+        return Ok(output
+            .contracts
+            .into_values()
+            .flatten()
+            .map(|(name, contract)| (name, CompactContract::from(contract).into_parts()))
+            .collect())
+    }
+
+    let layouts_path = project.paths.cache.with_file_name(STORAGE_LAYOUTS_CACHE_FILE);
+    *storage_layouts = std::fs::read_to_string(&layouts_path)
+        .ok()
+        .and_then(|layouts| serde_json::from_str(&layouts).ok())
+        .unwrap_or_default();
+
+    let output = if output.is_unchanged() {
+        output
+    } else {
+        // only the changed files were compiled, the up to date cache now has all artifacts
+        extend_layouts(&output.output(), storage_layouts);
+        project.compile()?
+    };
+    let contracts = artifacts(output);
+
+    let missing_layout = contracts.iter().any(|(name, _)| {
+        let contract_name = name.rsplit(':').next().unwrap_or(name);
+        !storage_layouts.contains_key(contract_name)
+    });
+    let contracts = if missing_layout {
+        storage_layouts.clear();
+        project.cached = false;
+        let contracts = compile(project)
+            .and_then(|output| contracts_with_layouts(project, output, storage_layouts));
+        project.cached = true;
+        contracts?
+    } else {
+        contracts
+    };
+
+    if let Err(err) = std::fs::write(&layouts_path, serde_json::to_string(storage_layouts)?) {
+        tracing::warn!(?err, "failed to write storage layout cache");
+    }
+    Ok(contracts)
+}
+
 /// A multi contract runner receives a set of contracts deployed in an EVM instance and proceeds
 /// to run all test functions in these contracts.
 pub struct MultiContractRunner {
@@ -122,6 +258,10 @@ pub struct MultiContractRunner {
     pub contracts: BTreeMap<String, (Abi, ethers::prelude::Bytes)>,
     /// Compiled contracts by name that have an Abi and runtime bytecode
     pub known_contracts: BTreeMap<String, (Abi, Vec<u8>)>,
+    /// Storage layouts of the compiled contracts by name, only collected for state diffs
+    pub storage_layouts: BTreeMap<String, StorageLayout>,
+    /// Index of all known function selectors and event topics, used to decode traces
+    pub signatures: SignatureCache,
     /// Identified contracts by test
     pub identified_contracts: BTreeMap<String, BTreeMap<Address, (String, Abi)>>,
     /// The EVM instance used in the test runner
@@ -132,6 +272,8 @@ pub struct MultiContractRunner {
     fuzzer: Option<TestRunner>,
     /// The address which will be used as the `from` field in all EVM calls
     sender: Option<Address>,
+    /// The external environment spawned to compile the contracts, shared by their runners
+    external: Option<Mutex<ExternalEvm>>,
 }
 
 impl MultiContractRunner {
@@ -142,6 +284,9 @@ impl MultiContractRunner {
         // TODO: Convert to iterator, ideally parallel one?
         let contracts = std::mem::take(&mut self.contracts);
 
+        // the total fuzzing time counts from the start of the test run
+        let fuzz_deadline = self.evm_opts.fuzz_max_total_time.map(|time| Instant::now() + time);
+
         let vicinity = self.evm_opts.vicinity()?;
         let backend = self.evm_opts.backend(&vicinity)?;
 
@@ -151,12 +296,22 @@ impl MultiContractRunner {
             .map(|(name, (abi, deploy_code))| {
                 // unavoidable duplication here?
                 let result = match backend {
-                    BackendKind::Simple(ref backend) => {
-                        self.run_tests(name, abi, backend, deploy_code.clone(), filter)?
-                    }
-                    BackendKind::Shared(ref backend) => {
-                        self.run_tests(name, abi, backend, deploy_code.clone(), filter)?
-                    }
+                    BackendKind::Simple(ref backend) => self.run_tests(
+                        name,
+                        abi,
+                        backend,
+                        deploy_code.clone(),
+                        filter,
+                        fuzz_deadline,
+                    )?,
+                    BackendKind::Shared(ref backend) => self.run_tests(
+                        name,
+                        abi,
+                        backend,
+                        deploy_code.clone(),
+                        filter,
+                        fuzz_deadline,
+                    )?,
                 };
                 Ok((name.clone(), result))
             })
@@ -169,6 +324,25 @@ impl MultiContractRunner {
         Ok(results)
     }
 
+    /// Returns the signatures of the test functions matching the filter by contract, without
+    /// running them
+    pub fn list(&self, filter: &impl TestFilter) -> BTreeMap<String, Vec<String>> {
+        self.contracts
+            .iter()
+            .filter(|(name, _)| filter.matches_contract(name))
+            .map(|(name, (abi, _))| {
+                let tests = abi
+                    .functions()
+                    .filter(|func| func.name.starts_with("test"))
+                    .filter(|func| filter.matches_test(&func.name))
+                    .map(|func| func.signature())
+                    .collect::<Vec<_>>();
+                (name.clone(), tests)
+            })
+            .filter(|(_, tests)| !tests.is_empty())
+            .collect()
+    }
+
     // The _name field is unused because we only want it for tracing
     #[tracing::instrument(
         name = "contract",
@@ -183,6 +357,7 @@ impl MultiContractRunner {
         backend: &B,
         deploy_code: ethers::prelude::Bytes,
         filter: &impl TestFilter,
+        fuzz_deadline: Option<Instant>,
     ) -> Result<BTreeMap<String, TestResult>> {
         let runner = ContractRunner::new(
             &self.evm_opts,
@@ -191,7 +366,9 @@ impl MultiContractRunner {
             contract,
             deploy_code,
             self.sender,
-        );
+            fuzz_deadline,
+        )
+        .with_external(self.external.as_ref());
         runner.run_tests(filter, self.fuzzer.clone(), Some(&self.known_contracts))
     }
 }
@@ -247,6 +424,17 @@ mod tests {
         assert!(only_gm["GmTest.json:GmTest"]["testGm()"].success);
     }
 
+    #[test]
+    fn test_list() {
+        let runner = runner();
+        let tests = runner.list(&Filter::new(".*", ".*"));
+        assert!(tests.get("Tests.json:Tests").is_none());
+        assert_eq!(tests["GmTest.json:GmTest"], vec!["testGm()".to_string()]);
+
+        let only_gm = runner.list(&Filter::new("testGm.*", ".*"));
+        assert_eq!(only_gm.len(), 1);
+    }
+
     fn test_abstract_contract() {
         let mut runner = runner();
         let results = runner.test(&Filter::new(".*", ".*")).unwrap();
@@ -257,6 +445,7 @@ mod tests {
 
     mod sputnik {
         use super::*;
+        use crate::{TestKind, TestKindGas};
         use std::collections::HashMap;
 
         #[test]
@@ -291,6 +480,17 @@ mod tests {
             );
         }
 
+        #[test]
+        fn test_sputnik_setup_failure_logs() {
+            let mut runner = runner();
+            let results = runner.test(&Filter::new(".*", "SetupTest")).unwrap();
+
+            let result = &results["SetupTest.json:SetupTest"]["testSetupBad()"];
+            assert!(!result.success);
+            assert!(result.logs.contains(&"setUp".to_owned()));
+            assert!(result.logs.contains(&"setUp console".to_owned()));
+        }
+
         #[test]
         fn test_sputnik_multi_runner() {
             test_multi_runner();
@@ -300,5 +500,56 @@ mod tests {
         fn test_sputnik_abstract_contract() {
             test_abstract_contract();
         }
+
+        #[test]
+        fn test_sputnik_fuzz_max_total_time() {
+            let evm_opts = EvmOpts {
+                fuzz_max_total_time: Some(std::time::Duration::ZERO),
+                ..EVM_OPTS.clone()
+            };
+            let mut runner = MultiContractRunnerBuilder::default()
+                .fuzzer(TestRunner::default())
+                .build(project(), evm_opts)
+                .unwrap();
+            let results = runner.test(&Filter::new("testFuzz.*", "GreeterTest")).unwrap();
+
+            // fuzz tests starting after the total fuzzing time run no cases
+            let tests = &results["GreeterTest.json:GreeterTest"];
+            assert!(!tests.is_empty());
+            for result in tests.values() {
+                match result.kind {
+                    TestKind::Fuzz(ref cases) => assert!(cases.cases().is_empty()),
+                    _ => panic!("expected a fuzz test"),
+                }
+            }
+        }
+
+        #[test]
+        fn test_sputnik_fuzz_max_time() {
+            let max_time = std::time::Duration::from_millis(100);
+            let evm_opts = EvmOpts { fuzz_max_time: Some(max_time), ..EVM_OPTS.clone() };
+            let mut runner = MultiContractRunnerBuilder::default()
+                .fuzzer(TestRunner::default())
+                .build(project(), evm_opts)
+                .unwrap();
+            let start = std::time::Instant::now();
+            let results = runner.test(&Filter::new("testFuzz.*", "GreeterTest")).unwrap();
+
+            // without a time limit the tests would run u32::MAX cases, the time check after each
+            // chunk of cases only overshoots it slightly
+            assert!(start.elapsed() < max_time * 50);
+            let tests = &results["GreeterTest.json:GreeterTest"];
+            assert!(!tests.is_empty());
+            for result in tests.values() {
+                let cases = match result.kind {
+                    TestKind::Fuzz(ref cases) => cases.cases().len(),
+                    _ => panic!("expected a fuzz test"),
+                };
+                assert!(cases > 0);
+                let gas = result.kind.gas_used();
+                assert!(matches!(gas, TestKindGas::Fuzz { runs, .. } if runs == cases));
+                assert!(gas.to_string().starts_with(&format!("(runs: {},", cases)));
+            }
+        }
     }
 }
@@ -1,6 +1,7 @@
 use crate::TestFilter;
 use evm_adapters::{
-    evm_opts::EvmOpts,
+    evm_opts::{EvmOpts, EvmType},
+    external::{ExternalEvm, ExternalState},
     sputnik::{helpers::TestSputnikVM, Executor, PRECOMPILES_MAP},
 };
 use rayon::iter::ParallelIterator;
@@ -13,11 +14,18 @@ use ethers::{
 use evm_adapters::{
     call_tracing::CallTraceArena,
     fuzz::{FuzzTestResult, FuzzedCases, FuzzedExecutor},
-    sputnik::cheatcodes::debugger::DebugArena,
+    sputnik::cheatcodes::{debugger::DebugArena, json::FileAccess},
+    state_diff::StateDiff,
     Evm, EvmError,
 };
 use eyre::Result;
-use std::{collections::BTreeMap, fmt, time::Instant};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::DerefMut,
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
 
 use proptest::test_runner::{TestError, TestRunner};
 use rayon::iter::IntoParallelRefIterator;
@@ -75,6 +83,10 @@ pub struct TestResult {
     /// Debug Steps
     #[serde(skip)]
     pub debug_calls: Option<Vec<DebugArena>>,
+
+    /// The changes the test made to the state, if enabled with `--state-diff`. Not available
+    /// for fuzz tests
+    pub state_diff: Option<StateDiff>,
 }
 
 impl TestResult {
@@ -140,6 +152,16 @@ impl TestKind {
     }
 }
 
+/// An external execution environment with the test contract deployed, shared by all tests of a
+/// contract. Every test starts from the state right after the deployment.
+struct ExternalTestEnv<'a> {
+    /// The environment spawned for the contract, or the one shared by all runners
+    evm: Box<dyn DerefMut<Target = ExternalEvm> + 'a>,
+    address: Address,
+    deployed: ExternalState,
+    logs: Vec<String>,
+}
+
 pub struct ContractRunner<'a, B> {
     // EVM Config Options
     /// The options used to instantiate a new EVM.
@@ -157,6 +179,10 @@ pub struct ContractRunner<'a, B> {
     pub code: ethers::prelude::Bytes,
     /// The address which will be used as the `from` field in all EVM calls
     pub sender: Address,
+    /// The instant after which fuzz tests stop running cases, if the total fuzzing time is capped
+    pub fuzz_deadline: Option<Instant>,
+    /// The external environment to run the tests in, instead of spawning one per contract
+    pub external: Option<&'a Mutex<ExternalEvm>>,
 }
 
 impl<'a, B: Backend> ContractRunner<'a, B> {
@@ -167,8 +193,37 @@ impl<'a, B: Backend> ContractRunner<'a, B> {
         contract: &'a Abi,
         code: ethers::prelude::Bytes,
         sender: Option<Address>,
+        fuzz_deadline: Option<Instant>,
     ) -> Self {
-        Self { evm_opts, evm_cfg, backend, contract, code, sender: sender.unwrap_or_default() }
+        Self {
+            evm_opts,
+            evm_cfg,
+            backend,
+            contract,
+            code,
+            sender: sender.unwrap_or_default(),
+            fuzz_deadline,
+            external: None,
+        }
+    }
+
+    /// Runs the tests in the already spawned external environment, which is locked while the
+    /// tests of the contract run
+    pub fn with_external(mut self, external: Option<&'a Mutex<ExternalEvm>>) -> Self {
+        self.external = external;
+        self
+    }
+
+    /// Returns a fuzzer for the EVM, running a fuzz test starting now for `--fuzz-max-time` if
+    /// set, and stopping at the deadline of all fuzz tests
+    fn fuzzed_executor<'b, E: Evm<S>, S>(
+        &self,
+        evm: &'b mut E,
+        runner: TestRunner,
+    ) -> FuzzedExecutor<'b, E, S> {
+        FuzzedExecutor::new(evm, runner, self.sender)
+            .run_until(self.evm_opts.fuzz_max_time.map(|time| Instant::now() + time))
+            .deadline(self.fuzz_deadline)
     }
 }
 
@@ -185,7 +240,11 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             self.evm_cfg,
             &*PRECOMPILES_MAP,
             self.evm_opts.ffi,
-            self.evm_opts.verbosity > 2,
+            FileAccess {
+                root: self.evm_opts.root.clone(),
+                allowed_paths: self.evm_opts.allowed_paths.clone(),
+            },
+            self.evm_opts.verbosity > 2 || self.evm_opts.trace_format.is_some(),
             self.evm_opts.debug,
         );
 
@@ -196,6 +255,23 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         Ok((addr, executor, logs))
     }
 
+    /// Deploys the test contract in the shared external execution environment, reset to the
+    /// state it was spawned with, or in a newly spawned one
+    fn new_external_env(&self, cmd: &str) -> Result<ExternalTestEnv<'a>> {
+        let mut evm: Box<dyn DerefMut<Target = ExternalEvm> + 'a> = match self.external {
+            Some(external) => {
+                let mut evm = external.lock().unwrap_or_else(PoisonError::into_inner);
+                evm.reset(ExternalState::default());
+                Box::new(evm)
+            }
+            None => Box::new(ExternalEvm::spawn(cmd)?),
+        };
+        let (address, _, _, logs) = evm.deploy(self.sender, self.code.clone(), 0u32.into())?;
+        evm.set_balance(address, self.evm_opts.initial_balance);
+        let deployed = evm.snapshot()?;
+        Ok(ExternalTestEnv { evm, address, deployed, logs })
+    }
+
     /// Runs all tests for a contract whose names match the provided regular expression
     pub fn run_tests(
         &self,
@@ -214,6 +290,21 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             .filter(|func| filter.matches_test(&func.name))
             .collect::<Vec<_>>();
 
+        // external environments are a single process shared by all tests, so these run one
+        // after the other
+        if let EvmType::External(ref cmd) = self.evm_opts.evm_type {
+            let mut env = self.new_external_env(cmd)?;
+            return test_fns
+                .iter()
+                .filter(|func| func.inputs.is_empty() || fuzzer.is_some())
+                .map(|func| {
+                    let fuzzer = fuzzer.clone().filter(|_| !func.inputs.is_empty());
+                    let result = self.run_test_external(&mut env, func, needs_setup, fuzzer)?;
+                    Ok((func.signature(), result))
+                })
+                .collect()
+        }
+
         // run all unit tests
         let unit_tests = test_fns
             .par_iter()
@@ -265,6 +356,10 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         let should_fail = func.name.starts_with("testFail");
         tracing::debug!(func = ?func.signature(), should_fail, "unit-testing");
 
+        if let EvmType::External(ref cmd) = self.evm_opts.evm_type {
+            return self.run_test_external(&mut self.new_external_env(cmd)?, func, setup, None)
+        }
+
         let (address, mut evm, init_logs) = self.new_sputnik_evm()?;
 
         let mut logs = init_logs;
@@ -281,6 +376,10 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             let setup_logs = match evm.setup(address) {
                 Ok((_reason, setup_logs)) => setup_logs,
                 Err(e) => {
+                    logs.extend(setup_failure_logs(&e));
+                    // add reverted logs
+                    logs.extend(evm.all_logs());
+
                     // if tracing is enabled, just return it as a failed test
                     // otherwise abort
                     if evm.tracing_enabled() {
@@ -307,12 +406,16 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
                         } else {
                             None
                         },
+                        state_diff: None,
                     })
                 }
             };
             logs.extend_from_slice(&setup_logs);
         }
 
+        // snapshot the state after the setup to diff the test's changes against
+        let pre_state = if self.evm_opts.state_diff { Some(evm.state().clone()) } else { None };
+
         let (status, reason, gas_used, logs) =
             match evm.call::<(), _, _>(self.sender, address, func.clone(), (), 0.into()) {
                 Ok((_, status, gas_used, execution_logs)) => {
@@ -341,6 +444,14 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             &mut evm,
         );
 
+        let state_diff = pre_state.map(|pre_state| {
+            let mut diff = evm.state().diff(&pre_state);
+            if let Some(known_contracts) = known_contracts {
+                diff.identify(known_contracts, |address| evm.code(address));
+            }
+            diff
+        });
+
         let success = evm.check_success(address, &status, should_fail);
         let duration = Instant::now().duration_since(start);
         tracing::debug!(?duration, %success, %gas_used);
@@ -355,6 +466,7 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             traces,
             identified_contracts,
             debug_calls: if evm.state().debug_enabled { Some(evm.debug_calls()) } else { None },
+            state_diff,
         })
     }
 
@@ -371,8 +483,15 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         let should_fail = func.name.starts_with("testFail");
         tracing::debug!(func = ?func.signature(), should_fail, "fuzzing");
 
+        if let EvmType::External(ref cmd) = self.evm_opts.evm_type {
+            let mut env = self.new_external_env(cmd)?;
+            return self.run_test_external(&mut env, func, setup, Some(runner))
+        }
+
         let (address, mut evm, init_logs) = self.new_sputnik_evm()?;
 
+        let mut logs = init_logs;
+
         let mut traces: Option<Vec<CallTraceArena>> = None;
         let mut identified_contracts: Option<BTreeMap<Address, (String, Abi)>> = None;
 
@@ -383,8 +502,12 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         if setup {
             tracing::trace!("setting up");
             match evm.setup(address) {
-                Ok((_reason, _setup_logs)) => {}
+                Ok((_reason, setup_logs)) => logs.extend(setup_logs),
                 Err(e) => {
+                    logs.extend(setup_failure_logs(&e));
+                    // add reverted logs
+                    logs.extend(evm.all_logs());
+
                     // if tracing is enabled, just return it as a failed test
                     // otherwise abort
                     if evm.tracing_enabled() {
@@ -401,7 +524,7 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
                         reason: Some("Setup failed: ".to_string() + &e.to_string()),
                         gas_used: 0,
                         counterexample: None,
-                        logs: vec![],
+                        logs,
                         kind: TestKind::Fuzz(FuzzedCases::new(vec![])),
                         traces,
                         identified_contracts,
@@ -410,17 +533,16 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
                         } else {
                             None
                         },
+                        state_diff: None,
                     })
                 }
             }
         }
 
-        let mut logs = init_logs;
-
         let prev = evm.set_tracing_enabled(false);
 
         // instantiate the fuzzed evm in line
-        let evm = FuzzedExecutor::new(&mut evm, runner, self.sender);
+        let evm = self.fuzzed_executor(&mut evm, runner);
         let FuzzTestResult { cases, test_error } = evm.fuzz(func, address, should_fail);
 
         let evm = evm.into_inner();
@@ -482,9 +604,113 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             traces,
             identified_contracts,
             debug_calls: if evm.state().debug_enabled { Some(evm.debug_calls()) } else { None },
+            state_diff: None,
         })
     }
 
+    /// Runs the test in an external execution environment, after reverting it to the state
+    /// right after the test contract was deployed. Fuzz tests are run if a fuzzer is provided.
+    /// Tracing and debugging are not available for external environments.
+    fn run_test_external(
+        &self,
+        env: &mut ExternalTestEnv<'_>,
+        func: &Function,
+        setup: bool,
+        fuzzer: Option<TestRunner>,
+    ) -> Result<TestResult> {
+        let start = Instant::now();
+        let should_fail = func.name.starts_with("testFail");
+
+        env.evm.reset(env.deployed.clone());
+        let (evm, address, mut logs) = (&mut **env.evm, env.address, env.logs.clone());
+
+        let is_fuzz = fuzzer.is_some();
+        let failed = |reason: String, gas_used: u64, logs: Vec<String>| TestResult {
+            success: false,
+            reason: Some(reason),
+            gas_used,
+            counterexample: None,
+            logs,
+            kind: if is_fuzz {
+                TestKind::Fuzz(FuzzedCases::new(vec![]))
+            } else {
+                TestKind::Standard(gas_used)
+            },
+            traces: None,
+            identified_contracts: None,
+            debug_calls: None,
+            state_diff: None,
+        };
+
+        if setup {
+            match evm.setup(address) {
+                Ok((_, setup_logs)) => logs.extend(setup_logs),
+                Err(e) => {
+                    logs.extend(setup_failure_logs(&e));
+                    return Ok(failed("Setup failed: ".to_string() + &e.to_string(), 0, logs))
+                }
+            }
+        }
+
+        let result = if let Some(runner) = fuzzer {
+            evm.snapshot()?;
+            let fuzzed = self.fuzzed_executor(evm, runner);
+            let FuzzTestResult { cases, test_error } = fuzzed.fuzz(func, address, should_fail);
+            let (reason, counterexample) = match test_error {
+                Some(err) => match err.test_error {
+                    TestError::Fail(_, value) => {
+                        let args = func.decode_input(&value.as_ref()[4..])?;
+                        let reason = Some(err.revert_reason).filter(|r| !r.is_empty());
+                        (reason, Some(CounterExample { calldata: value.clone(), args }))
+                    }
+                    result => panic!("Unexpected test result: {:?}", result),
+                },
+                None => (None, None),
+            };
+            TestResult {
+                success: counterexample.is_none(),
+                reason,
+                gas_used: cases.median_gas(),
+                counterexample,
+                logs,
+                kind: TestKind::Fuzz(cases),
+                traces: None,
+                identified_contracts: None,
+                debug_calls: None,
+                state_diff: None,
+            }
+        } else {
+            match evm.call::<(), _, _>(self.sender, address, func.clone(), (), 0.into()) {
+                Ok((_, status, gas_used, execution_logs)) => {
+                    logs.extend(execution_logs);
+                    TestResult {
+                        success: evm.check_success(address, &status, should_fail),
+                        reason: None,
+                        gas_used,
+                        counterexample: None,
+                        logs,
+                        kind: TestKind::Standard(gas_used),
+                        traces: None,
+                        identified_contracts: None,
+                        debug_calls: None,
+                        state_diff: None,
+                    }
+                }
+                Err(EvmError::Execution { reason, gas_used, logs: execution_logs }) => {
+                    logs.extend(execution_logs);
+                    let mut result = failed(reason, gas_used, logs);
+                    result.success = should_fail;
+                    result
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        let duration = Instant::now().duration_since(start);
+        tracing::debug!(?duration, success = %result.success, "external");
+        Ok(result)
+    }
+
     fn update_traces<S: Clone, E: Evm<S>>(
         &self,
         traces: &mut Option<Vec<CallTraceArena>>,
@@ -542,6 +768,14 @@ fn revert<S: Clone, E: Evm<S> + evm_adapters::Evm<S, ReturnReason = T>, T>(_evm:
     <E as evm_adapters::Evm<S>>::revert()
 }
 
+/// The logs emitted before a failed `setUp` reverted
+fn setup_failure_logs(err: &eyre::Report) -> Vec<String> {
+    match err.downcast_ref::<EvmError>() {
+        Some(EvmError::Execution { logs, .. }) => logs.clone(),
+        _ => vec![],
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -560,7 +794,7 @@ mod tests {
             abi: &'a Abi,
             code: ethers::prelude::Bytes,
         ) -> ContractRunner<'a, MemoryBackend<'a>> {
-            ContractRunner::new(&*EVM_OPTS, &*CFG_NO_LMT, &*BACKEND, abi, code, None)
+            ContractRunner::new(&*EVM_OPTS, &*CFG_NO_LMT, &*BACKEND, abi, code, None, None)
         }
 
         #[test]
@@ -0,0 +1,678 @@
+//! Storage layout comparison, used to detect upgrades which would corrupt a proxy's storage
+
+use ethers::{
+    solc::{artifacts::Contract, ArtifactOutput, Project},
+    types::U256,
+};
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+};
+
+/// The storage layout of a contract, as emitted by solc's `storageLayout` output
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageLayout {
+    pub storage: Vec<StorageVariable>,
+    #[serde(default)]
+    pub types: BTreeMap<String, StorageType>,
+}
+
+/// A single state variable in a [`StorageLayout`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StorageVariable {
+    pub label: String,
+    /// The storage slot, encoded as a decimal string
+    pub slot: String,
+    /// The offset in bytes within the slot
+    pub offset: u64,
+    /// The identifier of the variable's type in [`StorageLayout::types`]
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// The contract the variable was declared in, e.g. `src/Token.sol:Token`
+    #[serde(default)]
+    pub contract: String,
+}
+
+/// A type referenced by a [`StorageVariable`]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageType {
+    pub label: String,
+    #[serde(rename = "numberOfBytes")]
+    pub number_of_bytes: String,
+    #[serde(default)]
+    pub encoding: String,
+    /// The members of a struct, positioned relative to the struct's first slot
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<StorageVariable>,
+    /// The key type of a mapping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// The value type of a mapping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// The element type of an array
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+}
+
+/// The location of a variable in storage
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StoragePosition {
+    pub slot: U256,
+    pub offset: u64,
+}
+
+impl fmt::Display for StoragePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "slot {}", self.slot)?;
+        if self.offset > 0 {
+            write!(f, " (offset {})", self.offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// A difference between two storage layouts
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayoutChange {
+    /// A new variable was appended after all existing variables
+    Added { label: String, position: StoragePosition },
+    /// A new variable was inserted in between existing variables
+    Inserted { label: String, position: StoragePosition },
+    /// A variable was removed
+    Removed { label: String, position: StoragePosition },
+    /// A variable was renamed but otherwise kept its position and type
+    Renamed { from: String, to: String, position: StoragePosition },
+    /// A variable was moved to another position
+    Moved { label: String, from: StoragePosition, to: StoragePosition },
+    /// The type of a variable changed and it now occupies a different number of bytes
+    Resized { label: String, position: StoragePosition, from: String, to: String },
+    /// The type of a variable changed but it still occupies the same number of bytes
+    Retyped { label: String, position: StoragePosition, from: String, to: String },
+    /// The type of a variable kept its name but its members, or those of the types it contains,
+    /// are laid out differently, e.g. a member was inserted into a struct
+    Restructured { label: String, position: StoragePosition, ty: String },
+}
+
+impl LayoutChange {
+    /// Whether the change makes the new layout incompatible with existing storage
+    pub fn is_breaking(&self) -> bool {
+        !matches!(self, LayoutChange::Added { .. } | LayoutChange::Renamed { .. })
+    }
+}
+
+impl fmt::Display for LayoutChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutChange::Added { label, position } => {
+                write!(f, "added `{}` at {}", label, position)
+            }
+            LayoutChange::Inserted { label, position } => {
+                write!(f, "inserted `{}` at {}, which was previously occupied", label, position)
+            }
+            LayoutChange::Removed { label, position } => {
+                write!(f, "removed `{}` from {}", label, position)
+            }
+            LayoutChange::Renamed { from, to, position } => {
+                write!(f, "renamed `{}` to `{}` at {}", from, to, position)
+            }
+            LayoutChange::Moved { label, from, to } => {
+                write!(f, "moved `{}` from {} to {}", label, from, to)
+            }
+            LayoutChange::Resized { label, position, from, to } => {
+                write!(f, "resized `{}` at {} from `{}` to `{}`", label, position, from, to)
+            }
+            LayoutChange::Retyped { label, position, from, to } => {
+                write!(
+                    f,
+                    "changed the type of `{}` at {} from `{}` to `{}`",
+                    label, position, from, to
+                )
+            }
+            LayoutChange::Restructured { label, position, ty } => {
+                write!(f, "changed the layout of `{}` of `{}` at {}", ty, label, position)
+            }
+        }
+    }
+}
+
+impl StorageLayout {
+    /// Reads a storage layout from a json file.
+    ///
+    /// The file can either be a storage layout, an artifact with a `storageLayout` field or
+    /// solc's standard json output, in which case the `contract` name is required.
+    pub fn load(path: impl AsRef<Path>, contract: Option<&str>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        let json: serde_json::Value = serde_json::from_str(&content)?;
+        Self::from_json(json, contract)
+            .wrap_err_with(|| format!("no storage layout found in {}", path.display()))
+    }
+
+    /// Reads the storage layout of a contract in the compiler output, if it was requested with
+    /// [`StorageLayout::request`]
+    pub fn from_contract(contract: &Contract) -> Option<Self> {
+        let json = serde_json::to_value(contract).ok()?;
+        Self::from_json(json, None).ok()
+    }
+
+    /// Makes solc emit the storage layout of every contract of the project
+    pub fn request<A: ArtifactOutput>(project: &mut Project<A>) {
+        project
+            .solc_config
+            .settings
+            .output_selection
+            .values_mut()
+            .flat_map(|file| file.values_mut())
+            .for_each(|outputs| outputs.push("storageLayout".to_string()));
+    }
+
+    fn from_json(mut json: serde_json::Value, contract: Option<&str>) -> Result<Self> {
+        if let Some(contracts) = json.get_mut("contracts").and_then(|c| c.as_object_mut()) {
+            let name = contract.ok_or_else(|| {
+                eyre::eyre!("the file contains multiple contracts, please specify the contract")
+            })?;
+            json = contracts
+                .values_mut()
+                .find_map(|file| file.get_mut(name).map(serde_json::Value::take))
+                .ok_or_else(|| eyre::eyre!("contract `{}` not found", name))?;
+        }
+        if let Some(layout) = json.get_mut("storageLayout") {
+            json = layout.take();
+        }
+        Ok(serde_json::from_value(json)?)
+    }
+
+    fn position(var: &StorageVariable) -> Result<StoragePosition> {
+        let slot = U256::from_dec_str(&var.slot)
+            .map_err(|_| eyre::eyre!("invalid slot `{}` of `{}`", var.slot, var.label))?;
+        Ok(StoragePosition { slot, offset: var.offset })
+    }
+
+    /// Returns the positions of all variables, in order
+    fn positions(&self) -> Result<Vec<StoragePosition>> {
+        self.storage.iter().map(Self::position).collect()
+    }
+
+    /// Returns the index of the first byte at the position, counting 32 bytes per slot
+    fn byte_index(position: StoragePosition) -> U256 {
+        position.slot.saturating_mul(32u64.into()).saturating_add(position.offset.into())
+    }
+
+    /// Returns the index of the variable in this layout which is the same as `var` of the
+    /// `other` layout: the one with the same name declared in the same contract or, if the
+    /// contract was renamed, the only variable with that name in both layouts
+    fn find_var(&self, other: &StorageLayout, var: &StorageVariable) -> Option<usize> {
+        let same_label = |layout: &StorageLayout| {
+            layout
+                .storage
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.label == var.label)
+                .collect::<Vec<_>>()
+        };
+        self.storage
+            .iter()
+            .position(|v| v.contract == var.contract && v.label == var.label)
+            .or_else(|| match (same_label(self).as_slice(), same_label(other).len()) {
+                ([(i, _)], 1) => Some(*i),
+                _ => None,
+            })
+    }
+
+    /// Returns the human readable type of the variable
+    fn type_label<'a>(&'a self, var: &'a StorageVariable) -> &'a str {
+        self.types.get(&var.ty).map(|ty| ty.label.as_str()).unwrap_or(&var.ty)
+    }
+
+    fn type_size(&self, var: &StorageVariable) -> Option<&str> {
+        self.types.get(&var.ty).map(|ty| ty.number_of_bytes.as_str())
+    }
+
+    /// Whether the type `ty` of this layout is stored like the type `other_ty` of the `other`
+    /// layout, comparing the members of structs and the key, value and element types of mappings
+    /// and arrays recursively
+    fn same_type(
+        &self,
+        ty: &str,
+        other: &StorageLayout,
+        other_ty: &str,
+        seen: &mut BTreeSet<(String, String)>,
+    ) -> bool {
+        // recursive types are compared where they are first encountered
+        if !seen.insert((ty.to_string(), other_ty.to_string())) {
+            return true
+        }
+        let (a, b) = match (self.types.get(ty), other.types.get(other_ty)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return ty == other_ty,
+        };
+        let mut same_inner = |a: &Option<String>, b: &Option<String>| match (a, b) {
+            (Some(a), Some(b)) => self.same_type(a, other, b, seen),
+            (a, b) => a == b,
+        };
+        a.label == b.label &&
+            a.number_of_bytes == b.number_of_bytes &&
+            a.encoding == b.encoding &&
+            same_inner(&a.key, &b.key) &&
+            same_inner(&a.value, &b.value) &&
+            same_inner(&a.base, &b.base) &&
+            a.members.len() == b.members.len() &&
+            a.members.iter().zip(&b.members).all(|(m, n)| {
+                m.slot == n.slot &&
+                    m.offset == n.offset &&
+                    self.same_type(&m.ty, other, &n.ty, seen)
+            })
+    }
+
+    /// Returns the number of bytes the variable occupies, a full slot if its type is unknown
+    fn byte_size(&self, var: &StorageVariable) -> U256 {
+        self.type_size(var)
+            .and_then(|size| U256::from_dec_str(size).ok())
+            .unwrap_or_else(|| 32u64.into())
+    }
+
+    /// Returns the names of the variables stored in the slot, separated by `, ` if several are
+    /// packed into it. Slots of variables spanning multiple slots, like structs and fixed size
+    /// arrays, are named by their offset from the variable's first slot.
+    ///
+    /// Slots of mappings and dynamic arrays are derived from a hash, so they can not be named.
+    pub fn slot_label(&self, slot: U256) -> Option<String> {
+        let labels = self
+            .storage
+            .iter()
+            .filter_map(|var| {
+                let start = Self::position(var).ok()?.slot;
+                let slots = (self.byte_size(var) + 31) / 32;
+                if slot == start {
+                    Some(var.label.clone())
+                } else if slot > start && slot < start + slots {
+                    Some(format!("{}[+{}]", var.label, slot - start))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if labels.is_empty() {
+            None
+        } else {
+            Some(labels.join(", "))
+        }
+    }
+
+    /// Returns all changes required to go from this layout to the `new` one.
+    ///
+    /// Variables are matched by the contract they are declared in and their name, so that
+    /// variables with the same name in different contracts of the inheritance tree are not
+    /// confused.
+    pub fn diff(&self, new: &StorageLayout) -> Result<Vec<LayoutChange>> {
+        let old_positions = self.positions()?;
+        let new_positions = new.positions()?;
+        let mut changes = Vec::new();
+        // whether each new variable corresponds to an old one
+        let mut matched = vec![false; new.storage.len()];
+
+        for (old_var, &position) in self.storage.iter().zip(&old_positions) {
+            let index = match new.find_var(self, old_var) {
+                Some(index) => {
+                    matched[index] = true;
+                    let to = new_positions[index];
+                    if to != position {
+                        changes.push(LayoutChange::Moved {
+                            label: old_var.label.clone(),
+                            from: position,
+                            to,
+                        });
+                    }
+                    index
+                }
+                None => match new_positions
+                    .iter()
+                    .position(|to| *to == position)
+                    .filter(|index| self.find_var(new, &new.storage[*index]).is_none())
+                {
+                    Some(index) => {
+                        matched[index] = true;
+                        changes.push(LayoutChange::Renamed {
+                            from: old_var.label.clone(),
+                            to: new.storage[index].label.clone(),
+                            position,
+                        });
+                        index
+                    }
+                    None => {
+                        changes
+                            .push(LayoutChange::Removed { label: old_var.label.clone(), position });
+                        continue
+                    }
+                },
+            };
+
+            // the type is compared where the variable is now, also if it was moved
+            let new_var = &new.storage[index];
+            let position = new_positions[index];
+            let (from, to) = (self.type_label(old_var), new.type_label(new_var));
+            if self.type_size(old_var) != new.type_size(new_var) {
+                changes.push(LayoutChange::Resized {
+                    label: new_var.label.clone(),
+                    position,
+                    from: from.to_string(),
+                    to: to.to_string(),
+                });
+            } else if from != to {
+                changes.push(LayoutChange::Retyped {
+                    label: new_var.label.clone(),
+                    position,
+                    from: from.to_string(),
+                    to: to.to_string(),
+                });
+            } else if !self.same_type(&old_var.ty, new, &new_var.ty, &mut BTreeSet::new()) {
+                changes.push(LayoutChange::Restructured {
+                    label: new_var.label.clone(),
+                    position,
+                    ty: to.to_string(),
+                });
+            }
+        }
+
+        // new variables are appended if they start after the last byte used by the old layout,
+        // including the trailing slots of structs and fixed size arrays
+        let end = self
+            .storage
+            .iter()
+            .zip(&old_positions)
+            .map(|(var, position)| Self::byte_index(*position).saturating_add(self.byte_size(var)))
+            .max();
+        for ((new_var, &position), _) in
+            new.storage.iter().zip(&new_positions).zip(&matched).filter(|(_, matched)| !**matched)
+        {
+            let label = new_var.label.clone();
+            if end.map(|end| Self::byte_index(position) >= end).unwrap_or(true) {
+                changes.push(LayoutChange::Added { label, position });
+            } else {
+                changes.push(LayoutChange::Inserted { label, position });
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(vars: &[(&str, u64, u64, &str)]) -> StorageLayout {
+        let mut layout = StorageLayout::default();
+        for (label, slot, offset, ty) in vars {
+            layout.storage.push(StorageVariable {
+                label: label.to_string(),
+                slot: slot.to_string(),
+                offset: *offset,
+                ty: format!("t_{}", ty),
+                contract: "src/Token.sol:Token".to_string(),
+            });
+            let size = match *ty {
+                "uint128" => "16",
+                "address" => "20",
+                "bool" => "1",
+                _ => "32",
+            };
+            layout.types.insert(
+                format!("t_{}", ty),
+                StorageType {
+                    label: ty.to_string(),
+                    number_of_bytes: size.to_string(),
+                    encoding: "inplace".to_string(),
+                    ..Default::default()
+                },
+            );
+        }
+        layout
+    }
+
+    fn pos(slot: u64, offset: u64) -> StoragePosition {
+        StoragePosition { slot: slot.into(), offset }
+    }
+
+    #[test]
+    fn can_load_from_artifact_and_standard_json() {
+        let layout = serde_json::json!({
+            "storage": [{ "astId": 3, "contract": "A.sol:A", "label": "owner", "offset": 0, "slot": "0", "type": "t_address" }],
+            "types": { "t_address": { "encoding": "inplace", "label": "address", "numberOfBytes": "20" } }
+        });
+        let artifact = serde_json::json!({ "abi": [], "storageLayout": layout.clone() });
+        let output = serde_json::json!({ "contracts": { "A.sol": { "A": artifact.clone() } } });
+
+        let expected = StorageLayout::from_json(layout, None).unwrap();
+        assert_eq!(expected.storage[0].label, "owner");
+        assert_eq!(StorageLayout::from_json(artifact, None).unwrap(), expected);
+        assert_eq!(StorageLayout::from_json(output.clone(), Some("A")).unwrap(), expected);
+        assert!(StorageLayout::from_json(output.clone(), None).is_err());
+        assert!(StorageLayout::from_json(output, Some("B")).is_err());
+    }
+
+    #[test]
+    fn compatible_upgrade() {
+        let old = layout(&[("owner", 0, 0, "address"), ("supply", 1, 0, "uint256")]);
+        let new = layout(&[
+            ("admin", 0, 0, "address"),
+            ("supply", 1, 0, "uint256"),
+            ("paused", 2, 0, "bool"),
+        ]);
+        let changes = old.diff(&new).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                LayoutChange::Renamed {
+                    from: "owner".to_string(),
+                    to: "admin".to_string(),
+                    position: pos(0, 0)
+                },
+                LayoutChange::Added { label: "paused".to_string(), position: pos(2, 0) },
+            ]
+        );
+        assert!(!changes.iter().any(LayoutChange::is_breaking));
+    }
+
+    #[test]
+    fn breaking_upgrade() {
+        let old = layout(&[
+            ("owner", 0, 0, "address"),
+            ("supply", 1, 0, "uint256"),
+            ("cap", 2, 0, "uint256"),
+        ]);
+        let new = layout(&[
+            ("owner", 0, 0, "uint256"),
+            ("paused", 1, 0, "bool"),
+            ("supply", 2, 0, "uint256"),
+            ("cap", 3, 0, "int256"),
+        ]);
+        let changes = old.diff(&new).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                LayoutChange::Resized {
+                    label: "owner".to_string(),
+                    position: pos(0, 0),
+                    from: "address".to_string(),
+                    to: "uint256".to_string()
+                },
+                LayoutChange::Moved { label: "supply".to_string(), from: pos(1, 0), to: pos(2, 0) },
+                LayoutChange::Moved { label: "cap".to_string(), from: pos(2, 0), to: pos(3, 0) },
+                LayoutChange::Inserted { label: "paused".to_string(), position: pos(1, 0) },
+            ]
+        );
+        assert!(changes.iter().all(LayoutChange::is_breaking));
+
+        let old = layout(&[("a", 0, 0, "uint256")]);
+        let new = layout(&[("a", 0, 0, "int256")]);
+        assert_eq!(
+            old.diff(&new).unwrap(),
+            vec![LayoutChange::Retyped {
+                label: "a".to_string(),
+                position: pos(0, 0),
+                from: "uint256".to_string(),
+                to: "int256".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn labels_slots() {
+        let mut layout = layout(&[("owner", 0, 0, "address"), ("paused", 0, 20, "bool")]);
+        layout.storage.push(StorageVariable {
+            label: "config".to_string(),
+            slot: "1".to_string(),
+            offset: 0,
+            ty: "t_struct".to_string(),
+            contract: "src/Token.sol:Token".to_string(),
+        });
+        layout.types.insert(
+            "t_struct".to_string(),
+            StorageType {
+                label: "struct Token.Config".to_string(),
+                number_of_bytes: "64".to_string(),
+                encoding: "inplace".to_string(),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(layout.slot_label(0u64.into()).unwrap(), "owner, paused");
+        assert_eq!(layout.slot_label(1u64.into()).unwrap(), "config");
+        assert_eq!(layout.slot_label(2u64.into()).unwrap(), "config[+1]");
+        assert!(layout.slot_label(3u64.into()).is_none());
+    }
+
+    #[test]
+    fn new_variable_inside_trailing_struct_is_inserted() {
+        let mut old = layout(&[("owner", 0, 0, "address")]);
+        old.storage.push(StorageVariable {
+            label: "config".to_string(),
+            slot: "1".to_string(),
+            offset: 0,
+            ty: "t_struct".to_string(),
+            contract: "src/Token.sol:Token".to_string(),
+        });
+        old.types.insert(
+            "t_struct".to_string(),
+            StorageType {
+                label: "struct Token.Config".to_string(),
+                number_of_bytes: "64".to_string(),
+                encoding: "inplace".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let mut new = old.clone();
+        new.storage.push(layout(&[("paused", 2, 0, "bool")]).storage.remove(0));
+        new.types.extend(layout(&[("paused", 2, 0, "bool")]).types);
+        assert_eq!(
+            old.diff(&new).unwrap(),
+            vec![LayoutChange::Inserted { label: "paused".to_string(), position: pos(2, 0) }]
+        );
+
+        let mut new = old.clone();
+        new.storage.push(layout(&[("paused", 3, 0, "bool")]).storage.remove(0));
+        assert_eq!(
+            old.diff(&new).unwrap(),
+            vec![LayoutChange::Added { label: "paused".to_string(), position: pos(3, 0) }]
+        );
+    }
+
+    #[test]
+    fn matches_variables_by_contract() {
+        let mut old = layout(&[("value", 0, 0, "uint256"), ("value", 1, 0, "uint256")]);
+        old.storage[0].contract = "src/Base.sol:Base".to_string();
+        let mut new = layout(&[("value", 0, 0, "uint256"), ("value", 2, 0, "uint256")]);
+        new.storage[0].contract = "src/Base.sol:Base".to_string();
+
+        assert_eq!(
+            old.diff(&new).unwrap(),
+            vec![LayoutChange::Moved {
+                label: "value".to_string(),
+                from: pos(1, 0),
+                to: pos(2, 0)
+            }]
+        );
+
+        // unique names are still matched if the contract was renamed
+        let old = layout(&[("supply", 0, 0, "uint256")]);
+        let mut new = old.clone();
+        new.storage[0].contract = "src/TokenV2.sol:TokenV2".to_string();
+        assert!(old.diff(&new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn compares_types_of_moved_variables() {
+        let old = layout(&[("a", 0, 0, "uint256")]);
+        let new = layout(&[("x", 0, 0, "uint256"), ("a", 1, 0, "int256")]);
+        assert_eq!(
+            old.diff(&new).unwrap(),
+            vec![
+                LayoutChange::Moved { label: "a".to_string(), from: pos(0, 0), to: pos(1, 0) },
+                LayoutChange::Retyped {
+                    label: "a".to_string(),
+                    position: pos(1, 0),
+                    from: "uint256".to_string(),
+                    to: "int256".to_string()
+                },
+                LayoutChange::Inserted { label: "x".to_string(), position: pos(0, 0) },
+            ]
+        );
+    }
+
+    #[test]
+    fn compares_struct_members() {
+        // `mapping(address => Token.Config) configs` with `Config { uint256 cap; }`
+        let mut old = layout(&[("configs", 0, 0, "mapping"), ("cap", 0, 0, "uint256")]);
+        let cap = old.storage.remove(1);
+        old.types.insert(
+            "t_mapping".to_string(),
+            StorageType {
+                label: "mapping(address => struct Token.Config)".to_string(),
+                number_of_bytes: "32".to_string(),
+                encoding: "mapping".to_string(),
+                key: Some("t_address".to_string()),
+                value: Some("t_struct".to_string()),
+                ..Default::default()
+            },
+        );
+        old.types.insert(
+            "t_struct".to_string(),
+            StorageType {
+                label: "struct Token.Config".to_string(),
+                number_of_bytes: "32".to_string(),
+                encoding: "inplace".to_string(),
+                members: vec![cap],
+                ..Default::default()
+            },
+        );
+        assert!(old.diff(&old.clone()).unwrap().is_empty());
+
+        // the member changes its type but the struct keeps its name and size
+        let mut new = old.clone();
+        new.types.extend(layout(&[("cap", 0, 0, "int256")]).types);
+        new.types.get_mut("t_struct").unwrap().members[0].ty = "t_int256".to_string();
+        assert_eq!(
+            old.diff(&new).unwrap(),
+            vec![LayoutChange::Restructured {
+                label: "configs".to_string(),
+                position: pos(0, 0),
+                ty: "mapping(address => struct Token.Config)".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_slots() {
+        let old = layout(&[("owner", 0, 0, "address")]);
+        let mut new = old.clone();
+        new.storage[0].slot = "0x01".to_string();
+        assert!(old.diff(&new).is_err());
+    }
+}
@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: UNLICENSED
 pragma solidity =0.8.1;
 
+import "./console.sol";
+
 contract DsTestMini {
     bool public failed;
 
+    event log(string);
+
     function fail() private {
         failed = true;
     }
@@ -17,6 +21,8 @@ contract DsTestMini {
 
 contract SetupTest is DsTestMini {
     function setUp() public {
+        emit log("setUp");
+        console.log("setUp console");
         T t = new T(10);
     }
 
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: Unlicense
+pragma solidity >=0.4.22 <0.9.0;
+
+/// Logs to the forge test output, at `-vv` and above. The calls use the selectors of
+/// `hardhat/console.sol`, so they are no-ops when deployed to a live network.
+library console {
+    address constant CONSOLE_ADDRESS = address(0x000000000000000000636F6e736F6c652e6c6f67);
+
+    function _send(bytes memory payload) private view {
+        address consoleAddress = CONSOLE_ADDRESS;
+        assembly {
+            let payloadStart := add(payload, 32)
+            let payloadLength := mload(payload)
+            let r := staticcall(gas(), consoleAddress, payloadStart, payloadLength, 0, 0)
+        }
+    }
+
+    function log(string memory p0) internal view {
+        _send(abi.encodeWithSignature("log(string)", p0));
+    }
+
+    function log(string memory p0, uint256 p1) internal view {
+        _send(abi.encodeWithSignature("log(string,uint256)", p0, p1));
+    }
+
+    function log(string memory p0, address p1) internal view {
+        _send(abi.encodeWithSignature("log(string,address)", p0, p1));
+    }
+
+    function log(string memory p0, bool p1) internal view {
+        _send(abi.encodeWithSignature("log(string,bool)", p0, p1));
+    }
+
+    function log(string memory p0, string memory p1) internal view {
+        _send(abi.encodeWithSignature("log(string,string)", p0, p1));
+    }
+
+    function log(string memory p0, uint256 p1, uint256 p2) internal view {
+        _send(abi.encodeWithSignature("log(string,uint256,uint256)", p0, p1, p2));
+    }
+
+    function log(string memory p0, address p1, uint256 p2) internal view {
+        _send(abi.encodeWithSignature("log(string,address,uint256)", p0, p1, p2));
+    }
+
+    function log(string memory p0, string memory p1, uint256 p2) internal view {
+        _send(abi.encodeWithSignature("log(string,string,uint256)", p0, p1, p2));
+    }
+
+    function logUint(uint256 p0) internal view {
+        _send(abi.encodeWithSignature("log(uint256)", p0));
+    }
+
+    function logInt(int256 p0) internal view {
+        _send(abi.encodeWithSignature("logInt(int256)", p0));
+    }
+
+    function logAddress(address p0) internal view {
+        _send(abi.encodeWithSignature("log(address)", p0));
+    }
+
+    function logBool(bool p0) internal view {
+        _send(abi.encodeWithSignature("log(bool)", p0));
+    }
+
+    function logBytes(bytes memory p0) internal view {
+        _send(abi.encodeWithSignature("logBytes(bytes)", p0));
+    }
+
+    function logBytes32(bytes32 p0) internal view {
+        _send(abi.encodeWithSignature("logBytes32(bytes32)", p0));
+    }
+}
@@ -21,7 +21,7 @@ use std::{
     thread,
 };
 
-use evm_adapters::sputnik::cheatcodes::debugger::DebugStep;
+use evm_adapters::sputnik::cheatcodes::debugger::{breakpoints, DebugStep};
 use eyre::Result;
 use tui::{
     backend::{Backend, CrosstermBackend},
@@ -176,7 +176,7 @@ impl Tui {
         let block_controls = Block::default();
 
         let text_output = Text::from(Span::styled(
-            "[q]: Quit | [k/j]: prev/next op | [a/s]: prev/next jump | [c/C]: prev/next call | [g/G]: start/end",
+            "[q]: Quit | [k/j]: prev/next op | [a/s]: prev/next jump | [c/C]: prev/next call | [b/B]: prev/next breakpoint | [g/G]: start/end",
             Style::default().add_modifier(Modifier::DIM)
         ));
         let paragraph = Paragraph::new(text_output)
@@ -703,6 +703,12 @@ impl Ui for Tui {
         let mut draw_memory: DrawMemory = DrawMemory::default();
 
         let debug_call: Vec<(Address, Vec<DebugStep>, bool)> = self.debug_arena.clone();
+        let breakpoints = breakpoints(&debug_call);
+        // start at the first breakpoint, if any were set
+        if let Some((_, call, step)) = breakpoints.first() {
+            draw_memory.inner_call_index = *call;
+            self.current_step = *step;
+        }
         let mut opcode_list: Vec<String> =
             debug_call[0].1.iter().map(|step| step.pretty_opcode()).collect();
         let mut last_index = 0;
@@ -783,6 +789,30 @@ impl Ui for Tui {
                         }
                         self.key_buffer.clear();
                     }
+                    // Go to previous breakpoint
+                    KeyCode::Char('b') => {
+                        let current = (draw_memory.inner_call_index, self.current_step);
+                        if let Some((_, call, step)) = breakpoints
+                            .iter()
+                            .rev()
+                            .find(|(_, call, step)| (*call, *step) < current)
+                        {
+                            draw_memory.inner_call_index = *call;
+                            self.current_step = *step;
+                        }
+                        self.key_buffer.clear();
+                    }
+                    // Go to next breakpoint
+                    KeyCode::Char('B') => {
+                        let current = (draw_memory.inner_call_index, self.current_step);
+                        if let Some((_, call, step)) =
+                            breakpoints.iter().find(|(_, call, step)| (*call, *step) > current)
+                        {
+                            draw_memory.inner_call_index = *call;
+                            self.current_step = *step;
+                        }
+                        self.key_buffer.clear();
+                    }
                     // Step forward
                     KeyCode::Char('s') => {
                         for _ in 0..Tui::buffer_as_number(&self.key_buffer, 1) {
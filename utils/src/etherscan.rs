@@ -0,0 +1,200 @@
+//! Identifies external contracts by their verified source on Etherscan
+use ethers_core::{
+    abi::Abi,
+    types::{Address, Chain, H256},
+};
+use ethers_etherscan::Client;
+use eyre::Result;
+use std::{collections::BTreeMap, path::PathBuf, str::FromStr};
+
+/// The ABI Etherscan returns for contracts without verified source
+const NOT_VERIFIED: &str = "Contract source code not verified";
+
+/// The chains supported by Etherscan: their chain id, name and the url of their Etherscan API
+const ETHERSCAN_CHAINS: &[(u64, &str, &str)] = &[
+    (1, "mainnet", "https://api.etherscan.io/api"),
+    (3, "ropsten", "https://api-ropsten.etherscan.io/api"),
+    (4, "rinkeby", "https://api-rinkeby.etherscan.io/api"),
+    (5, "goerli", "https://api-goerli.etherscan.io/api"),
+    (42, "kovan", "https://api-kovan.etherscan.io/api"),
+    (10, "optimism", "https://api-optimistic.etherscan.io/api"),
+    (69, "optimism-kovan", "https://api-kovan-optimistic.etherscan.io/api"),
+    (100, "xdai", "https://blockscout.com/xdai/mainnet/api"),
+    (137, "polygon", "https://api.polygonscan.com/api"),
+    (80001, "polygon_mumbai", "https://api-testnet.polygonscan.com/api"),
+    (43114, "avalanche", "https://api.snowtrace.io/api"),
+    (43113, "avalanche_fuji", "https://api-testnet.snowtrace.io/api"),
+];
+
+/// Returns the chain with the given chain id, if Etherscan supports it
+pub fn etherscan_chain(chain_id: u64) -> Option<Chain> {
+    let (_, name, _) = ETHERSCAN_CHAINS.iter().find(|(id, _, _)| *id == chain_id)?;
+    Chain::from_str(name).ok()
+}
+
+/// Returns the url of the Etherscan API of the chain
+pub fn etherscan_api_url(chain: Chain) -> Option<&'static str> {
+    ETHERSCAN_CHAINS
+        .iter()
+        .find(|(_, name, _)| Chain::from_str(name).ok() == Some(chain))
+        .map(|(_, _, url)| *url)
+}
+
+/// Returns the hash of the transaction which created the contract at the address
+pub async fn contract_creation_tx(chain_id: u64, api_key: &str, address: Address) -> Result<H256> {
+    let url = etherscan_chain(chain_id)
+        .and_then(etherscan_api_url)
+        .ok_or_else(|| eyre::eyre!("chain {} is not supported by Etherscan", chain_id))?;
+    let contract = format!("{:?}", address);
+    let res: serde_json::Value = reqwest::Client::new()
+        .get(url)
+        .query(&[
+            ("module", "contract"),
+            ("action", "getcontractcreation"),
+            ("contractaddresses", contract.as_str()),
+            ("apikey", api_key),
+        ])
+        .send()
+        .await?
+        .json()
+        .await?;
+    let tx_hash = res
+        .get("result")
+        .and_then(|result| result.get(0))
+        .and_then(|creation| creation.get("txHash"))
+        .and_then(|tx_hash| tx_hash.as_str())
+        .ok_or_else(|| eyre::eyre!("no creation transaction found for {:?}: {}", address, res))?;
+    Ok(tx_hash.parse()?)
+}
+
+/// Resolves names and ABIs of contracts from their verified source on Etherscan.
+///
+/// Results, including contracts without verified source, are cached as one file per address in
+/// the cache directory, so every address is only ever looked up once.
+pub struct EtherscanIdentifier {
+    client: Client,
+    cache_dir: Option<PathBuf>,
+}
+
+impl EtherscanIdentifier {
+    pub fn new(
+        chain: Chain,
+        api_key: impl Into<String>,
+        cache_dir: Option<PathBuf>,
+    ) -> Result<Self> {
+        Ok(Self { client: Client::new(chain, api_key)?, cache_dir })
+    }
+
+    /// Returns the name and ABI of the contract at the address, or `None` if its source is not
+    /// verified
+    pub async fn identify(&self, address: Address) -> Result<Option<(String, Abi)>> {
+        if let Some(cached) = self.read_cache(address) {
+            return Ok(cached)
+        }
+
+        let source = self.client.contract_source_code(address).await?;
+        let identified = match source.items.into_iter().next() {
+            Some(item) if item.abi != NOT_VERIFIED => Some((item.contract_name, item.abi)),
+            _ => None,
+        };
+        self.write_cache(address, identified.as_ref());
+
+        identified.map(|(name, abi)| Ok((name, serde_json::from_str(&abi)?))).transpose()
+    }
+
+    /// Identifies all addresses, skipping the ones which could not be looked up
+    pub async fn identify_all(
+        &self,
+        addresses: impl IntoIterator<Item = Address>,
+    ) -> BTreeMap<Address, (String, Abi)> {
+        let mut identified = BTreeMap::new();
+        for address in addresses {
+            match self.identify(address).await {
+                Ok(Some(contract)) => {
+                    identified.insert(address, contract);
+                }
+                Ok(None) => {}
+                Err(err) => tracing::warn!(?err, ?address, "could not fetch contract"),
+            }
+        }
+        identified
+    }
+
+    fn cache_file(&self, address: Address) -> Option<PathBuf> {
+        self.cache_dir.as_ref().map(|dir| dir.join(format!("{:?}.json", address)))
+    }
+
+    /// Returns `Some` if the address was looked up before, with `None` inside for contracts
+    /// without verified source
+    #[allow(clippy::option_option)]
+    fn read_cache(&self, address: Address) -> Option<Option<(String, Abi)>> {
+        let content = std::fs::read_to_string(self.cache_file(address)?).ok()?;
+        let cached: serde_json::Value = serde_json::from_str(&content).ok()?;
+        if cached.is_null() {
+            return Some(None)
+        }
+        let name = cached.get("name")?.as_str()?.to_string();
+        let abi = serde_json::from_value(cached.get("abi")?.clone()).ok()?;
+        Some(Some((name, abi)))
+    }
+
+    fn write_cache(&self, address: Address, identified: Option<&(String, String)>) {
+        let file = match self.cache_file(address) {
+            Some(file) => file,
+            None => return,
+        };
+        let entry = match identified {
+            Some((name, abi)) => match serde_json::from_str::<serde_json::Value>(abi) {
+                Ok(abi) => serde_json::json!({ "name": name, "abi": abi }),
+                Err(_) => return,
+            },
+            None => serde_json::Value::Null,
+        };
+        let res = file
+            .parent()
+            .map(std::fs::create_dir_all)
+            .transpose()
+            .and_then(|_| std::fs::write(&file, entry.to_string()));
+        if let Err(err) = res {
+            tracing::warn!(?err, "could not cache etherscan response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_cached_contracts() {
+        let dir = std::env::temp_dir().join("foundry-etherscan-cache-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let identifier = EtherscanIdentifier::new(Chain::Mainnet, "", Some(dir.clone())).unwrap();
+
+        let verified = Address::from_low_u64_be(1);
+        let unverified = Address::from_low_u64_be(2);
+        let abi = r#"[{"type":"function","name":"swap","inputs":[],"outputs":[],"stateMutability":"nonpayable"}]"#;
+        identifier.write_cache(verified, Some(&("Router".to_string(), abi.to_string())));
+        identifier.write_cache(unverified, None);
+
+        let (name, abi) = identifier.read_cache(verified).unwrap().unwrap();
+        assert_eq!(name, "Router");
+        assert!(abi.function("swap").is_ok());
+        assert!(matches!(identifier.read_cache(unverified), Some(None)));
+        assert!(identifier.read_cache(Address::from_low_u64_be(3)).is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn maps_chain_ids() {
+        assert_eq!(etherscan_chain(1), Some(Chain::Mainnet));
+        assert_eq!(etherscan_chain(31337), None);
+
+        // every supported chain has an API url
+        for (chain_id, _, url) in ETHERSCAN_CHAINS {
+            let chain = etherscan_chain(*chain_id).unwrap();
+            assert_eq!(etherscan_api_url(chain), Some(*url));
+        }
+    }
+}
@@ -13,6 +13,8 @@ use eyre::{Result, WrapErr};
 use serde::Deserialize;
 use std::{collections::HashSet, env::VarError};
 
+pub mod etherscan;
+
 const BASE_TX_COST: u64 = 21000;
 
 /// Helper trait for converting types to Functions. Helpful for allowing the `call`